@@ -1,24 +1,242 @@
+#include <chrono>
+#include <condition_variable>
+#include <cstring>
 #include <memory>
 #include <mutex>
 #include <optional>
+#include <string>
+#include <thread>
+#include <unordered_set>
 #include <vector>
 
 #include "node_embedding_api.h"
 
+#include "env-inl.h"
 #include "node.h"
 #include "node_api.h"
+#include "node_binding.h"
+#include "node_buffer.h"
+#include "node_options-inl.h"
+#include "node_process.h"
 
 #include "uv.h"
 
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <pthread.h>
+#include <time.h>
+#endif
+
 #include "v8.h"
 
 namespace {
-std::mutex env_mutex;
-node::Environment* env_ptr = nullptr;
+class Watchdog;
+}
+
+struct node_instance_s {
+  std::mutex mutex;
+  // Notified when a run ends, used by node_instance_shutdown.
+  std::condition_variable stopped;
+  node::Environment* env = nullptr;
+  // Set by node_instance_terminate, reset when the next run starts.
+  bool terminated = false;
+  Watchdog* watchdog = nullptr;  // set while env is set
+  // The node_shutdown calls using the instance, guarded by running_mutex.
+  int shutdowns = 0;
+};
+
+namespace {
+// Instances that are currently running an environment. Used by node_stop().
+std::mutex running_mutex;
+std::unordered_set<node_instance_t*> running_instances;
+// Notified when node_shutdown stops using its instances.
+std::condition_variable shutdowns_done;
+
+// Waits until no node_shutdown call uses the instance, so it can be destroyed.
+void wait_for_shutdowns(node_instance_t* instance) {
+  std::unique_lock<std::mutex> lock(running_mutex);
+  shutdowns_done.wait(lock, [&]() { return instance->shutdowns == 0; });
+}
+
+// Process-wide state. V8 cannot be re-initialized after it has been disposed,
+// so the platform is created on the first run and kept alive until the
+// process exits.
+std::mutex init_mutex;
+bool initialized = false;  // guarded by init_mutex
+bool args_set_up = false;  // guarded by init_mutex
+// The V8 options of the run that initialized the process, guarded by
+// init_mutex. Later runs have to pass the same ones.
+std::vector<std::string> process_v8_args;
+node::MultiIsolatePlatform* platform = nullptr;
+
+// Copies the arguments into one block that is never freed. libuv keeps argv[0]
+// as the process title buffer and assumes that the strings are contiguous, so
+// it needs memory that outlives the arguments of the run.
+char** copy_process_args(int argc, const char* const* argv) {
+  size_t size = 0;
+  for (int i = 0; i < argc; i++) {
+    size += strlen(argv[i]) + 1;
+  }
+
+  char** args = new char*[argc + 1];
+  char* data = new char[size];
+  for (int i = 0; i < argc; i++) {
+    size_t length = strlen(argv[i]) + 1;
+    memcpy(data, argv[i], length);
+    args[i] = data;
+    data += length;
+  }
+  args[argc] = nullptr;
+  return args;
+}
+
+// Runs node::InitializeOncePerProcess and sets up V8 unless an earlier call
+// has. Returns nullptr if the process was initialized before, and sets
+// `v8_args_differ` if `v8_args` are not the ones it was initialized with. A
+// result that returns early, such as for errors or --version, is not kept, so
+// the next call initializes the process again.
+std::unique_ptr<node::InitializationResult> initialize_once(
+    int argc,
+    const char* const* argv,
+    const std::vector<std::string>& args,
+    const std::vector<std::string>& v8_args,
+    bool* v8_args_differ) {
+  std::lock_guard<std::mutex> guard(init_mutex);
+  if (initialized) {
+    *v8_args_differ = v8_args != process_v8_args;
+    return nullptr;
+  }
+
+  if (!args_set_up) {
+    args_set_up = true;
+    uv_setup_args(argc, copy_process_args(argc, argv));
+  }
+  std::unique_ptr<node::InitializationResult> result =
+      node::InitializeOncePerProcess(
+          args,
+          {node::ProcessInitializationFlags::kNoInitializeV8,
+           node::ProcessInitializationFlags::kNoInitializeNodeV8Platform});
+  if (result->early_return() != 0) {
+    return result;
+  }
+
+  initialized = true;
+  process_v8_args = v8_args;
+  platform = node::MultiIsolatePlatform::Create(4).release();
+  v8::V8::InitializePlatform(platform);
+  v8::V8::Initialize();
+  return result;
+}
+
+// Parses the arguments like node::InitializeOncePerProcess, without applying
+// the options to the process. Node.js options are moved from `args` to
+// `exec_args`. Returns nullptr and sets `errors` if they are invalid.
+// `v8_args` receives the options that are passed to V8, if not null.
+std::shared_ptr<node::PerProcessOptions> parse_options(
+    std::vector<std::string>* args,
+    std::vector<std::string>* exec_args,
+    std::vector<std::string>* errors,
+    std::vector<std::string>* v8_args = nullptr) {
+  auto options = std::make_shared<node::PerProcessOptions>();
+  std::vector<std::string> parsed_v8_args;
+  node::options_parser::Parse(args,
+                              exec_args,
+                              &parsed_v8_args,
+                              options.get(),
+                              node::kDisallowedInEnvvar,
+                              errors);
+  if (v8_args != nullptr && !parsed_v8_args.empty()) {
+    // The first one is the program name
+    v8_args->assign(parsed_v8_args.begin() + 1, parsed_v8_args.end());
+  }
+  if (!errors->empty()) {
+    return nullptr;
+  }
+  return options;
+}
+
+// Applies the options of a run after the first one to its environment. The
+// per-process and V8 options of the first run are kept.
+void apply_run_options(
+    node::CommonEnvironmentSetup* setup,
+    const std::shared_ptr<node::PerIsolateOptions>& run_options) {
+  if (!run_options) {
+    return;
+  }
+  *setup->env()->options() = *run_options->per_env;
+  setup->isolate_data()->set_options(run_options);
+}
+
+void set_env(node_instance_t* instance,
+             node::Environment* env,
+             Watchdog* watchdog) {
+  {
+    std::lock_guard<std::mutex> guard(instance->mutex);
+    instance->env = env;
+    instance->watchdog = watchdog;
+    if (env != nullptr) {
+      instance->terminated = false;
+    }
+  }
+  instance->stopped.notify_all();
+
+  std::lock_guard<std::mutex> guard(running_mutex);
+  if (env != nullptr) {
+    running_instances.insert(instance);
+  } else {
+    running_instances.erase(instance);
+  }
+}
+
+// Copies the string to memory allocated with malloc().
+char* copy_string(const std::string& value) {
+  char* c_result = (char*)malloc(value.size() + 1);
+  value.copy(c_result, value.size());
+  c_result[value.size()] = '\0';
+  return c_result;
+}
+
+char* copy_string(const std::optional<std::string>& value) {
+  return value ? copy_string(*value) : nullptr;
+}
+
+// Must be called with the instance mutex held and the instance running.
+int terminate_locked(node_instance_t* instance) {
+  instance->terminated = true;
+  return node::Stop(instance->env);
+}
+
+// Emits the `shutdown` event on the event loop thread and waits until the run
+// ends or the deadline passes, then terminates the run. Returns 0 if the run
+// ended before the deadline, 1 if it was terminated and -1 if the instance is
+// not running.
+int shutdown_instance(node_instance_t* instance,
+                      std::chrono::steady_clock::time_point deadline) {
+  std::unique_lock<std::mutex> lock(instance->mutex);
+  node::Environment* env = instance->env;
+  if (env == nullptr) {
+    return -1;
+  }
+
+  env->SetImmediateThreadsafe([](node::Environment* env) {
+    if (!env->can_call_into_js()) {
+      return;
+    }
 
-void set_env(node::Environment* env) {
-  std::lock_guard<std::mutex> guard(env_mutex);
-  env_ptr = env;
+    v8::HandleScope handle_scope(env->isolate());
+    v8::Context::Scope context_scope(env->context());
+    USE(node::ProcessEmit(env, "shutdown", v8::Undefined(env->isolate())));
+  });
+
+  // Also true if a later run has already started on the instance.
+  if (instance->stopped.wait_until(
+          lock, deadline, [&]() { return instance->env != env; })) {
+    return 0;
+  }
+
+  terminate_locked(instance);
+  return 1;
 }
 
 char* join_errors(const std::vector<std::string>& errors) {
@@ -29,10 +247,671 @@ char* join_errors(const std::vector<std::string>& errors) {
     }
     joined_error += errors[i];
   }
-  char* c_result = (char*)malloc(joined_error.size() + 1);
-  joined_error.copy(c_result, joined_error.size());
-  c_result[joined_error.size()] = '\0';
-  return c_result;
+  return copy_string(joined_error);
+}
+
+// Creates an environment variable store that is independent of the process
+// environment, like the one used by worker threads.
+std::shared_ptr<node::KVStore> create_env_vars(
+    v8::Isolate* isolate, const std::vector<std::string>& env_vars) {
+  std::shared_ptr<node::KVStore> store = node::KVStore::CreateMapKVStore();
+  for (const std::string& env_var : env_vars) {
+    std::size_t separator = env_var.find('=');
+    if (separator == std::string::npos) {
+      continue;
+    }
+
+    std::string name = env_var.substr(0, separator);
+    std::string value = env_var.substr(separator + 1);
+    store->Set(isolate,
+               v8::String::NewFromUtf8(isolate, name.c_str()).ToLocalChecked(),
+               v8::String::NewFromUtf8(isolate, value.c_str()).ToLocalChecked());
+  }
+
+  return store;
+}
+
+// Reads the input of process.stdin using the embedder's callback. The
+// callback may block, so it is called on a separate thread, one read at a
+// time. The thread is detached and may outlive the environment if the
+// callback blocks, the embedder's data is released once it has finished.
+class InputReader : public std::enable_shared_from_this<InputReader> {
+ public:
+  static constexpr std::size_t kChunkSize = 64 * 1024;
+
+  InputReader(node_input_callback_t read,
+              node_input_unread_t unread,
+              node_input_release_t release,
+              void* data)
+      : read_(read), unread_(unread), release_(release), data_(data) {}
+
+  ~InputReader() {
+    if (release_ != nullptr) {
+      release_(data_);
+    }
+  }
+
+  void Init(node::Environment* env) {
+    env_ = env;
+    async_ = new uv_async_t();
+    async_->data = this;
+    uv_async_init(env->event_loop(), async_, OnResult);
+    uv_unref(reinterpret_cast<uv_handle_t*>(async_));
+  }
+
+  // Reads the next chunk and calls `callback` with an error, or with a buffer
+  // that is null at the end of the input. Keeps the event loop alive until
+  // the chunk has been read.
+  void Read(v8::Local<v8::Function> callback) {
+    callback_.Reset(env_->isolate(), callback);
+    uv_ref(reinterpret_cast<uv_handle_t*>(async_));
+    {
+      std::lock_guard<std::mutex> guard(mutex_);
+      requested_ = true;
+      if (!thread_started_) {
+        thread_started_ = true;
+        std::thread(ThreadMain, shared_from_this()).detach();
+      }
+    }
+    condition_.notify_one();
+  }
+
+  // Must be called on the event loop thread before the environment is freed.
+  // A chunk that has been read, but not passed to JavaScript, is unread.
+  void Close() {
+    std::vector<char> pending;
+    {
+      std::lock_guard<std::mutex> guard(mutex_);
+      closed_ = true;
+      if (has_result_) {
+        pending = std::move(chunk_);
+      }
+    }
+    condition_.notify_one();
+    Unread(pending);
+    callback_.Reset();
+    env_->CloseHandle(async_, [](uv_async_t* async) { delete async; });
+  }
+
+ private:
+  static void ThreadMain(std::shared_ptr<InputReader> self) {
+    for (;;) {
+      {
+        std::unique_lock<std::mutex> lock(self->mutex_);
+        self->condition_.wait(
+            lock, [&]() { return self->closed_ || self->requested_; });
+        if (self->closed_) {
+          return;
+        }
+        self->requested_ = false;
+      }
+
+      std::vector<char> chunk(kChunkSize);
+      int length = self->read_(self->data_, chunk.data(), chunk.size());
+      chunk.resize(length > 0 ? length : 0);
+
+      std::unique_lock<std::mutex> lock(self->mutex_);
+      if (self->closed_) {
+        lock.unlock();
+        self->Unread(chunk);
+        return;
+      }
+      self->result_ = length;
+      self->chunk_ = std::move(chunk);
+      self->has_result_ = true;
+      uv_async_send(self->async_);
+    }
+  }
+
+  void Unread(const std::vector<char>& chunk) {
+    if (unread_ != nullptr && !chunk.empty()) {
+      unread_(data_, chunk.data(), chunk.size());
+    }
+  }
+
+  static void OnResult(uv_async_t* async) {
+    auto* self = static_cast<InputReader*>(async->data);
+    uv_unref(reinterpret_cast<uv_handle_t*>(async));
+
+    int length;
+    std::vector<char> chunk;
+    {
+      std::lock_guard<std::mutex> guard(self->mutex_);
+      length = self->result_;
+      chunk = std::move(self->chunk_);
+      self->has_result_ = false;
+    }
+
+    if (self->callback_.IsEmpty()) {
+      return;
+    }
+
+    v8::Isolate* isolate = self->env_->isolate();
+    v8::HandleScope handle_scope(isolate);
+    v8::Local<v8::Context> context = self->env_->context();
+    v8::Context::Scope context_scope(context);
+    v8::Local<v8::Function> callback = self->callback_.Get(isolate);
+    self->callback_.Reset();
+
+    v8::Local<v8::Value> argv[2] = {v8::Null(isolate), v8::Null(isolate)};
+    if (length < 0) {
+      argv[0] = v8::Exception::Error(
+          v8::String::NewFromUtf8Literal(isolate, "Failed to read the input"));
+    } else if (length > 0) {
+      argv[1] = node::Buffer::Copy(isolate, chunk.data(), chunk.size())
+                    .ToLocalChecked();
+    }
+
+    node::MakeCallback(isolate,
+                       context->Global(),
+                       callback,
+                       2,
+                       argv,
+                       node::async_context{0, 0});
+  }
+
+  node_input_callback_t read_;
+  node_input_unread_t unread_;
+  node_input_release_t release_;
+  void* data_;
+  node::Environment* env_ = nullptr;
+  uv_async_t* async_ = nullptr;
+  v8::Global<v8::Function> callback_;
+
+  std::mutex mutex_;
+  std::condition_variable condition_;
+  bool thread_started_ = false;
+  bool requested_ = false;
+  bool closed_ = false;
+  int result_ = 0;
+  bool has_result_ = false;  // set until OnResult takes chunk_
+  std::vector<char> chunk_;
+};
+
+// Measures the CPU time used by the thread that created it.
+class ThreadCpuClock {
+ public:
+  ThreadCpuClock() {
+#ifdef _WIN32
+    DuplicateHandle(GetCurrentProcess(),
+                    GetCurrentThread(),
+                    GetCurrentProcess(),
+                    &thread_,
+                    0,
+                    FALSE,
+                    DUPLICATE_SAME_ACCESS);
+#else
+    pthread_getcpuclockid(pthread_self(), &clock_);
+#endif
+  }
+
+  ~ThreadCpuClock() {
+#ifdef _WIN32
+    CloseHandle(thread_);
+#endif
+  }
+
+  ThreadCpuClock(const ThreadCpuClock&) = delete;
+  ThreadCpuClock& operator=(const ThreadCpuClock&) = delete;
+
+  // May be called from any thread.
+  std::chrono::nanoseconds Now() const {
+#ifdef _WIN32
+    FILETIME creation, exit, kernel, user;
+    if (!GetThreadTimes(thread_, &creation, &exit, &kernel, &user)) {
+      return {};
+    }
+    auto ticks = [](const FILETIME& time) {
+      return (static_cast<uint64_t>(time.dwHighDateTime) << 32) |
+             time.dwLowDateTime;
+    };
+    // FILETIME counts in units of 100 nanoseconds
+    return std::chrono::nanoseconds((ticks(kernel) + ticks(user)) * 100);
+#else
+    timespec time;
+    if (clock_gettime(clock_, &time) != 0) {
+      return {};
+    }
+    return std::chrono::seconds(time.tv_sec) +
+           std::chrono::nanoseconds(time.tv_nsec);
+#endif
+  }
+
+ private:
+#ifdef _WIN32
+  HANDLE thread_ = nullptr;
+#else
+  clockid_t clock_;
+#endif
+};
+
+// Enforces the time limits of a run, and of single calls made with
+// node_instance_begin_time_limit, on a separate thread. The thread is only
+// started once a limit is set. Created on the event loop thread, and must be
+// destroyed before the environment is freed.
+class Watchdog {
+ public:
+  Watchdog(node::Environment* env, uint64_t wall_ms, uint64_t cpu_ms)
+      : env_(env), run_(MakeBudget(wall_ms, cpu_ms)) {
+    if (run_.wall || run_.cpu) {
+      std::lock_guard<std::mutex> guard(mutex_);
+      StartLocked();
+    }
+  }
+
+  ~Watchdog() {
+    {
+      std::lock_guard<std::mutex> guard(mutex_);
+      stopping_ = true;
+    }
+    wake_.notify_one();
+    if (thread_.joinable()) {
+      thread_.join();
+    }
+  }
+
+  // Which limit of the run was exceeded, if any.
+  node_time_limit_t run_exceeded() {
+    std::lock_guard<std::mutex> guard(mutex_);
+    return run_exceeded_;
+  }
+
+  // Called on the event loop thread before a call. The JavaScript is
+  // terminated once the call exceeds a limit, but the environment keeps
+  // running.
+  void Begin(uint64_t wall_ms, uint64_t cpu_ms) {
+    {
+      std::lock_guard<std::mutex> guard(mutex_);
+      call_ = MakeBudget(wall_ms, cpu_ms);
+      call_exceeded_ = NODE_TIME_LIMIT_NONE;
+      StartLocked();
+    }
+    wake_.notify_one();
+  }
+
+  // Called on the event loop thread after the call has returned. Returns
+  // which limit of the call was exceeded, if any.
+  node_time_limit_t End() {
+    std::lock_guard<std::mutex> guard(mutex_);
+    node_time_limit_t exceeded = call_exceeded_;
+    call_ = {};
+    call_exceeded_ = NODE_TIME_LIMIT_NONE;
+    // The termination is pending if the call returned meanwhile. It must be
+    // cancelled so JavaScript can run again, unless the run is stopping.
+    if (exceeded != NODE_TIME_LIMIT_NONE && !env_->is_stopping()) {
+      env_->isolate()->CancelTerminateExecution();
+    }
+    return exceeded;
+  }
+
+ private:
+  using Clock = std::chrono::steady_clock;
+
+  // CPU time can't be waited for, so it is checked at this interval.
+  static constexpr std::chrono::milliseconds kCpuPollInterval{10};
+
+  struct Budget {
+    std::optional<Clock::time_point> wall;
+    std::optional<std::chrono::nanoseconds> cpu;
+  };
+
+  Budget MakeBudget(uint64_t wall_ms, uint64_t cpu_ms) {
+    Budget budget;
+    if (wall_ms != 0) {
+      budget.wall = Clock::now() + std::chrono::milliseconds(wall_ms);
+    }
+    if (cpu_ms != 0) {
+      budget.cpu = cpu_clock_.Now() + std::chrono::milliseconds(cpu_ms);
+    }
+    return budget;
+  }
+
+  static node_time_limit_t Check(const Budget& budget,
+                                 Clock::time_point now,
+                                 std::chrono::nanoseconds cpu_now) {
+    if (budget.wall && now >= *budget.wall) {
+      return NODE_TIME_LIMIT_WALL;
+    }
+    if (budget.cpu && cpu_now >= *budget.cpu) {
+      return NODE_TIME_LIMIT_CPU;
+    }
+    return NODE_TIME_LIMIT_NONE;
+  }
+
+  void StartLocked() {
+    if (!thread_.joinable()) {
+      thread_ = std::thread([this]() { Run(); });
+    }
+  }
+
+  void Run() {
+    std::unique_lock<std::mutex> lock(mutex_);
+    while (!stopping_) {
+      Clock::time_point now = Clock::now();
+      std::chrono::nanoseconds cpu_now = cpu_clock_.Now();
+
+      if (run_exceeded_ == NODE_TIME_LIMIT_NONE) {
+        run_exceeded_ = Check(run_, now, cpu_now);
+        if (run_exceeded_ != NODE_TIME_LIMIT_NONE) {
+          // Also terminates the JavaScript that is running.
+          node::Stop(env_);
+        }
+      }
+      if (call_exceeded_ == NODE_TIME_LIMIT_NONE) {
+        call_exceeded_ = Check(call_, now, cpu_now);
+        if (call_exceeded_ != NODE_TIME_LIMIT_NONE) {
+          env_->isolate()->TerminateExecution();
+        }
+      }
+
+      std::optional<Clock::time_point> wake_at;
+      auto wake_for = [&](const Budget& budget, node_time_limit_t exceeded) {
+        if (exceeded != NODE_TIME_LIMIT_NONE) {
+          return;
+        }
+        if (budget.wall && (!wake_at || *budget.wall < *wake_at)) {
+          wake_at = budget.wall;
+        }
+        if (budget.cpu && (!wake_at || now + kCpuPollInterval < *wake_at)) {
+          wake_at = now + kCpuPollInterval;
+        }
+      };
+      wake_for(run_, run_exceeded_);
+      wake_for(call_, call_exceeded_);
+
+      if (wake_at) {
+        wake_.wait_until(lock, *wake_at);
+      } else {
+        wake_.wait(lock);
+      }
+    }
+  }
+
+  node::Environment* env_;
+  ThreadCpuClock cpu_clock_;
+  std::mutex mutex_;
+  std::condition_variable wake_;
+  std::thread thread_;
+  bool stopping_ = false;
+  Budget run_;
+  Budget call_;
+  node_time_limit_t run_exceeded_ = NODE_TIME_LIMIT_NONE;
+  node_time_limit_t call_exceeded_ = NODE_TIME_LIMIT_NONE;
+};
+
+// State of a run that is reported by the main script.
+struct run_state_t {
+  node_output_callback_t output_callback = nullptr;
+  void* output_data = nullptr;
+  std::shared_ptr<InputReader> input;
+  node_console_callback_t console_callback = nullptr;
+  void* console_data = nullptr;
+  node_resolve_callback_t resolve_callback = nullptr;
+  void* resolve_data = nullptr;
+  node::Environment* env = nullptr;
+  size_t heap_limit = 0;
+  node_heap_limit_callback_t heap_limit_callback = nullptr;
+  void* heap_limit_data = nullptr;
+  bool out_of_memory = false;
+  bool uncaught_exception = false;
+  std::optional<std::string> exception_name;
+  std::optional<std::string> exception_message;
+  std::optional<std::string> exception_stack;
+  std::optional<std::string> exception_cause;
+};
+
+std::optional<std::string> get_string_arg(
+    const v8::FunctionCallbackInfo<v8::Value>& info, int index) {
+  if (!info[index]->IsString()) {
+    return std::nullopt;
+  }
+
+  v8::String::Utf8Value value(info.GetIsolate(), info[index]);
+  return std::string(*value, value.length());
+}
+
+// Called by the main script when an exception is not handled by JavaScript,
+// with the name, message, stack and cause of the exception as strings.
+// Only the first exception is kept.
+void ReportUncaughtException(const v8::FunctionCallbackInfo<v8::Value>& info) {
+  auto* state =
+      static_cast<run_state_t*>(info.Data().As<v8::External>()->Value());
+  if (state->uncaught_exception) {
+    return;
+  }
+
+  state->uncaught_exception = true;
+  state->exception_name = get_string_arg(info, 0);
+  state->exception_message = get_string_arg(info, 1);
+  state->exception_stack = get_string_arg(info, 2);
+  state->exception_cause = get_string_arg(info, 3);
+}
+
+// Called by the main script with the file descriptor and a buffer written to
+// process.stdout or process.stderr. Returns false if the callback failed.
+void WriteOutput(const v8::FunctionCallbackInfo<v8::Value>& info) {
+  auto* state =
+      static_cast<run_state_t*>(info.Data().As<v8::External>()->Value());
+  if (!info[0]->IsInt32() || !info[1]->IsArrayBufferView()) {
+    info.GetReturnValue().Set(false);
+    return;
+  }
+
+  int fd = info[0].As<v8::Int32>()->Value();
+  v8::Local<v8::ArrayBufferView> chunk = info[1].As<v8::ArrayBufferView>();
+  std::vector<char> data(chunk->ByteLength());
+  chunk->CopyContents(data.data(), data.size());
+  int result =
+      state->output_callback(state->output_data, fd, data.data(), data.size());
+  info.GetReturnValue().Set(result == 0);
+}
+
+// Called by the main script with a callback that receives the next chunk of
+// input for process.stdin, see InputReader::Read.
+void ReadInput(const v8::FunctionCallbackInfo<v8::Value>& info) {
+  auto* state =
+      static_cast<run_state_t*>(info.Data().As<v8::External>()->Value());
+  if (info[0]->IsFunction()) {
+    state->input->Read(info[0].As<v8::Function>());
+  }
+}
+
+// Called by the main script with the method name, the formatted message and
+// the inspected arguments of a console call.
+void LogConsole(const v8::FunctionCallbackInfo<v8::Value>& info) {
+  auto* state =
+      static_cast<run_state_t*>(info.Data().As<v8::External>()->Value());
+  v8::Isolate* isolate = info.GetIsolate();
+  v8::Local<v8::Context> context = isolate->GetCurrentContext();
+  if (!info[0]->IsString() || !info[1]->IsString() || !info[2]->IsArray()) {
+    return;
+  }
+
+  v8::String::Utf8Value method(isolate, info[0]);
+  v8::String::Utf8Value message(isolate, info[1]);
+  v8::Local<v8::Array> args = info[2].As<v8::Array>();
+  std::vector<std::string> arg_strings;
+  for (uint32_t i = 0; i < args->Length(); ++i) {
+    v8::Local<v8::Value> arg;
+    if (!args->Get(context, i).ToLocal(&arg)) {
+      return;
+    }
+    v8::String::Utf8Value arg_string(isolate, arg);
+    arg_strings.emplace_back(*arg_string, arg_string.length());
+  }
+
+  std::vector<const char*> argv;
+  for (const std::string& arg : arg_strings) {
+    argv.push_back(arg.c_str());
+  }
+
+  node_console_message_t console_message{*method,
+                                         *message,
+                                         static_cast<size_t>(message.length()),
+                                         static_cast<int>(argv.size()),
+                                         argv.data()};
+  state->console_callback(state->console_data, &console_message);
+}
+
+// Called by the main script with the specifier, the referrer and whether it
+// is imported, before Node.js resolves a module.
+void ResolveModule(const v8::FunctionCallbackInfo<v8::Value>& info) {
+  auto* state =
+      static_cast<run_state_t*>(info.Data().As<v8::External>()->Value());
+  v8::Isolate* isolate = info.GetIsolate();
+  std::optional<std::string> specifier = get_string_arg(info, 0);
+  std::optional<std::string> referrer = get_string_arg(info, 1);
+  if (!specifier) {
+    return;
+  }
+
+  std::string value;
+  node_resolve_value_t set_value =
+      [](void* target, const char* data, size_t length) {
+        static_cast<std::string*>(target)->assign(data, length);
+      };
+  node_resolve_type_t type =
+      state->resolve_callback(state->resolve_data,
+                              specifier->c_str(),
+                              referrer ? referrer->c_str() : nullptr,
+                              info[2]->IsTrue() ? 1 : 0,
+                              set_value,
+                              &value);
+  if (type == NODE_RESOLVE_DEFAULT) {
+    return;
+  }
+
+  v8::Local<v8::String> value_string;
+  if (!v8::String::NewFromUtf8(isolate,
+                               value.data(),
+                               v8::NewStringType::kNormal,
+                               static_cast<int>(value.size()))
+           .ToLocal(&value_string)) {
+    return;
+  }
+
+  if (type == NODE_RESOLVE_ERROR) {
+    isolate->ThrowException(v8::Exception::Error(value_string));
+    return;
+  }
+
+  v8::Local<v8::Value> resolved[] = {v8::Integer::New(isolate, type),
+                                     value_string};
+  info.GetReturnValue().Set(v8::Array::New(isolate, resolved, 2));
+}
+
+// Called when the heap of the environment has reached `limit`. Returns the
+// limit raised by the heap limit callback, or 0 if the environment was stopped
+// because it is out of memory.
+size_t OnHeapLimit(run_state_t* state, size_t limit, size_t used) {
+  if (state->out_of_memory) {
+    return 0;
+  }
+
+  if (state->heap_limit_callback != nullptr) {
+    size_t new_limit =
+        state->heap_limit_callback(state->heap_limit_data, limit, used);
+    if (new_limit > limit) {
+      return new_limit;
+    }
+  }
+
+  state->out_of_memory = true;
+  node::Stop(state->env);
+  return 0;
+}
+
+// Called by V8 instead of aborting the process when the heap is full.
+size_t NearHeapLimit(void* data,
+                     size_t current_heap_limit,
+                     size_t initial_heap_limit) {
+  auto* state = static_cast<run_state_t*>(data);
+  v8::HeapStatistics stats;
+  state->env->isolate()->GetHeapStatistics(&stats);
+  size_t new_limit =
+      OnHeapLimit(state, current_heap_limit, stats.used_heap_size());
+  if (new_limit != 0) {
+    return new_limit;
+  }
+
+  // Gives the current GC some leeway to finish, like Node.js does for
+  // workers. No JavaScript runs after the environment has been stopped.
+  constexpr size_t kExtraHeapAllowance = 16 * 1024 * 1024;
+  return current_heap_limit + kExtraHeapAllowance;
+}
+
+// Enforces the `heap_limit` option after each garbage collection.
+void CheckHeapLimit(v8::Isolate* isolate,
+                    v8::GCType type,
+                    v8::GCCallbackFlags flags,
+                    void* data) {
+  auto* state = static_cast<run_state_t*>(data);
+  v8::HeapStatistics stats;
+  isolate->GetHeapStatistics(&stats);
+  if (stats.used_heap_size() <= state->heap_limit) {
+    return;
+  }
+
+  size_t new_limit =
+      OnHeapLimit(state, state->heap_limit, stats.used_heap_size());
+  if (new_limit != 0) {
+    state->heap_limit = new_limit;
+  }
+}
+
+// Initializes the `__embedder_internal` binding, which lets the main script
+// report to the embedding layer.
+void InitializeInternalBinding(v8::Local<v8::Object> exports,
+                               v8::Local<v8::Value> module,
+                               v8::Local<v8::Context> context,
+                               void* priv) {
+  v8::Isolate* isolate = context->GetIsolate();
+  v8::Local<v8::External> state = v8::External::New(isolate, priv);
+  auto set_function = [&](const char* name, v8::FunctionCallback callback) {
+    v8::Local<v8::Function> function =
+        v8::Function::New(context, callback, state).ToLocalChecked();
+    exports
+        ->Set(context,
+              v8::String::NewFromUtf8(isolate, name).ToLocalChecked(),
+              function)
+        .Check();
+  };
+
+  set_function("reportUncaughtException", ReportUncaughtException);
+  if (static_cast<run_state_t*>(priv)->output_callback != nullptr) {
+    set_function("writeOutput", WriteOutput);
+  }
+  if (static_cast<run_state_t*>(priv)->input != nullptr) {
+    set_function("readInput", ReadInput);
+  }
+  if (static_cast<run_state_t*>(priv)->console_callback != nullptr) {
+    set_function("logConsole", LogConsole);
+  }
+  if (static_cast<run_state_t*>(priv)->resolve_callback != nullptr) {
+    set_function("resolveModule", ResolveModule);
+  }
+}
+
+// The native module that is being initialized on the current thread. N-API
+// init functions can't take data, so InitNativeModule reads it from here.
+thread_local const node_module_t* initializing_module = nullptr;
+
+napi_value InitNativeModule(napi_env env, napi_value exports) {
+  void* result = initializing_module->init(
+      initializing_module->data, static_cast<void*>(env), exports);
+  return result != nullptr ? static_cast<napi_value>(result) : exports;
+}
+
+// Initializes a linked binding of node_options_t.modules as an N-API module.
+void InitializeNativeModule(v8::Local<v8::Object> exports,
+                            v8::Local<v8::Value> module,
+                            v8::Local<v8::Context> context,
+                            void* priv) {
+  // A module may load another module while it is initialized.
+  const node_module_t* previous = initializing_module;
+  initializing_module = static_cast<const node_module_t*>(priv);
+  napi_module_register_by_symbol(exports, module, context, InitNativeModule);
+  initializing_module = previous;
 }
 
 std::vector<std::string> create_arg_vec(int argc, const char* const* argv) {
@@ -46,22 +925,210 @@ std::vector<std::string> create_arg_vec(int argc, const char* const* argv) {
   return vec;
 }
 
-node_run_result_t RunNodeInstance(node::MultiIsolatePlatform* platform,
-                                  const std::vector<std::string>& args,
-                                  const std::vector<std::string>& exec_args,
-                                  napi_addon_register_func napi_reg_func) {
+// Initializes the process on the first call, and returns the arguments and
+// exec arguments of an environment. Later runs get their own options in
+// `run_options`, and have to pass the V8 options of the first run. Returns
+// NODE_RUN_INVALID_OPTIONS or NODE_RUN_INIT_FAILED and sets the exit code and
+// error if the arguments are invalid or the process failed to initialize.
+node_run_status_t create_env_args(const node_options_t& options,
+                     std::vector<std::string>* args,
+                     std::vector<std::string>* exec_args,
+                     std::shared_ptr<node::PerIsolateOptions>* run_options,
+                     int* exit_code,
+                     char** error) {
+  std::vector<std::string> process_args =
+      create_arg_vec(options.process_argc, options.process_argv);
+  std::vector<std::string> requested_exec_args =
+      create_arg_vec(options.exec_argc, options.exec_argv);
+  std::vector<std::string> script_args =
+      create_arg_vec(options.script_argc, options.script_argv);
+  if (process_args.empty()) {
+    process_args.emplace_back("node");
+  }
+  process_args.insert(process_args.begin() + 1,
+                      requested_exec_args.begin(),
+                      requested_exec_args.end());
+
+  // Checked first, so invalid arguments never reach the process options
+  *args = process_args;
+  exec_args->clear();
+  std::vector<std::string> errors;
+  std::vector<std::string> v8_args;
+  std::shared_ptr<node::PerProcessOptions> parsed =
+      parse_options(args, exec_args, &errors, &v8_args);
+  if (!parsed) {
+    *exit_code = static_cast<int>(node::ExitCode::kInvalidCommandLineArgument);
+    *error = join_errors(errors);
+    return NODE_RUN_INVALID_OPTIONS;
+  }
+
+  bool v8_args_differ = false;
+  std::unique_ptr<node::InitializationResult> result =
+      initialize_once(options.process_argc,
+                      options.process_argv,
+                      process_args,
+                      v8_args,
+                      &v8_args_differ);
+  if (result && result->early_return() != 0) {
+    *exit_code = result->exit_code();
+    *error = join_errors(result->errors());
+    return NODE_RUN_INIT_FAILED;
+  }
+  if (v8_args_differ) {
+    *exit_code = static_cast<int>(node::ExitCode::kInvalidCommandLineArgument);
+    *error = copy_string(std::string(
+        "V8 options can only be set by the first run in a process, later runs "
+        "have to pass the same ones"));
+    return NODE_RUN_INVALID_OPTIONS;
+  }
+
+  if (result) {
+    *args = result->args();
+    *exec_args = result->exec_args();
+  } else {
+    *run_options = parsed->per_isolate;
+  }
+  args->insert(args->end(), script_args.begin(), script_args.end());
+  return NODE_RUN_OK;
+}
+
+// Snapshots start with this header, one value per line, so they are only used
+// with the Node.js build that created them.
+constexpr const char* snapshot_magic = "node-embedding-snapshot";
+constexpr const char* snapshot_format_version = "1";
+
+std::vector<std::string> snapshot_header_values() {
+  return {snapshot_magic,
+          snapshot_format_version,
+          NODE_VERSION,
+          v8::V8::GetVersion(),
+          std::string(NODE_ARCH) + "-" + NODE_PLATFORM};
+}
+
+std::string snapshot_header() {
+  std::string header;
+  for (const std::string& value : snapshot_header_values()) {
+    header += value + '\n';
+  }
+  return header;
+}
+
+// Checks the header of a snapshot. Returns false and sets `error` if the
+// snapshot can't be used by this build. Sets `header_length` otherwise.
+bool check_snapshot(const char* data,
+                    size_t length,
+                    size_t* header_length,
+                    std::string* error) {
+  static const char* const names[] = {
+      nullptr, "format version", "Node.js version", "V8 version", "platform"};
+  std::vector<std::string> expected = snapshot_header_values();
+
+  size_t offset = 0;
+  for (size_t i = 0; i < expected.size(); ++i) {
+    const char* end = static_cast<const char*>(
+        memchr(data + offset, '\n', length - offset));
+    if (end == nullptr) {
+      *error = "The data is not a Node.js snapshot";
+      return false;
+    }
+
+    std::string value(data + offset, end);
+    offset = end - data + 1;
+    if (value == expected[i]) {
+      continue;
+    }
+
+    if (i == 0) {
+      *error = "The data is not a Node.js snapshot";
+    } else {
+      *error = std::string("The snapshot was created for the ") + names[i] +
+               " " + value + ", but this is " + expected[i];
+    }
+    return false;
+  }
+
+  if (header_length != nullptr) {
+    *header_length = offset;
+  }
+  return true;
+}
+
+// Returns null and sets `error` if the snapshot is invalid.
+node::EmbedderSnapshotData::Pointer load_snapshot(const char* data,
+                                                  size_t length,
+                                                  std::string* error) {
+  size_t header_length = 0;
+  if (!check_snapshot(data, length, &header_length, error)) {
+    return {};
+  }
+
+  std::vector<char> blob(data + header_length, data + length);
+  node::EmbedderSnapshotData::Pointer snapshot =
+      node::EmbedderSnapshotData::FromBlob(blob);
+  if (!snapshot) {
+    *error = "The snapshot data is corrupted";
+    return {};
+  }
+  if (!snapshot->CanUseCustomSnapshotPerIsolate()) {
+    *error = "This Node.js build does not support custom snapshots";
+    return {};
+  }
+
+  return snapshot;
+}
+
+node_run_result_t RunNodeInstance(
+    node_instance_t* instance,
+    node::MultiIsolatePlatform* platform,
+    const std::vector<std::string>& args,
+    const std::vector<std::string>& exec_args,
+    const std::shared_ptr<node::PerIsolateOptions>& run_options,
+    const std::vector<std::string>* env_vars,
+    std::shared_ptr<InputReader> input,
+    const node::EmbedderSnapshotData* snapshot,
+    const node_options_t& options) {
   std::vector<std::string> errors;
   std::unique_ptr<node::CommonEnvironmentSetup> setup =
-      node::CommonEnvironmentSetup::Create(platform, &errors, args, exec_args);
+      snapshot != nullptr
+          ? node::CommonEnvironmentSetup::CreateFromSnapshot(
+                platform, &errors, snapshot, args, exec_args)
+          : node::CommonEnvironmentSetup::Create(
+                platform, &errors, args, exec_args);
 
   if (!setup) {
-    return {1, join_errors(errors)};
+    return {1, join_errors(errors), NODE_RUN_INIT_FAILED};
   }
+  apply_run_options(setup.get(), run_options);
 
   v8::Isolate* isolate = setup->isolate();
   node::Environment* env = setup->env();
 
-  node_run_result_t result{0, nullptr};
+  // Copied, as the linked bindings keep pointers to the modules and their
+  // names while the environment runs.
+  std::vector<std::string> module_names;
+  std::vector<node_module_t> native_modules;
+  for (int i = 0; i < options.module_count; ++i) {
+    module_names.emplace_back(options.modules[i].name);
+  }
+  for (int i = 0; i < options.module_count; ++i) {
+    native_modules.push_back({module_names[i].c_str(),
+                              options.modules[i].init,
+                              options.modules[i].data});
+  }
+
+  node_run_result_t result{0, nullptr, NODE_RUN_OK};
+  run_state_t state;
+  state.output_callback = options.output_callback;
+  state.output_data = options.output_data;
+  state.input = std::move(input);
+  state.console_callback = options.console_callback;
+  state.console_data = options.console_data;
+  state.resolve_callback = options.resolve_callback;
+  state.resolve_data = options.resolve_data;
+  state.env = env;
+  state.heap_limit = options.heap_limit;
+  state.heap_limit_callback = options.heap_limit_callback;
+  state.heap_limit_data = options.heap_limit_data;
   node::SetProcessExitHandler(env, [&](node::Environment* env, int exit_code) {
     result.exit_code = exit_code;
   });
@@ -72,32 +1139,91 @@ node_run_result_t RunNodeInstance(node::MultiIsolatePlatform* platform,
     v8::HandleScope handle_scope(isolate);
     v8::Context::Scope context_scope(setup->context());
 
+    if (env_vars != nullptr) {
+      env->set_env_vars(create_env_vars(isolate, *env_vars));
+    }
+
+    if (state.input != nullptr) {
+      state.input->Init(env);
+    }
+
+    // Removed again before the state goes out of scope.
+    isolate->AddNearHeapLimitCallback(NearHeapLimit, &state);
+    if (state.heap_limit != 0) {
+      isolate->AddGCEpilogueCallback(CheckHeapLimit, &state);
+    }
+
     node::AddLinkedBinding(env,
                            napi_module{
                                NAPI_MODULE_VERSION,
                                node::ModuleFlags::kLinked,
                                nullptr,
-                               napi_reg_func,
+                               napi_addon_register_func(options.napi_reg_func),
                                "__embedder_mod",
                                nullptr,
                                {0},
                            });
+    node::AddLinkedBinding(
+        env, "__embedder_internal", InitializeInternalBinding, &state);
+    for (const node_module_t& native_module : native_modules) {
+      node::AddLinkedBinding(env,
+                             native_module.name,
+                             InitializeNativeModule,
+                             const_cast<node_module_t*>(&native_module));
+    }
 
-    v8::MaybeLocal<v8::Value> loadenv_ret = node::LoadEnvironment(
-        env,
-        "const publicRequire = require('module').createRequire(process.cwd() "
-        "+ '/');"
-        "globalThis.require = publicRequire;"
-        "globalThis.embedVars = { nön_ascıı: '🏳️‍🌈' };"
-        "process._linkedBinding('__embedder_mod');");
+    Watchdog watchdog(env, options.time_limit_ms, options.cpu_time_limit_ms);
+
+    // Set before the main script runs, so a script that never returns can
+    // still be terminated.
+    set_env(instance, env, &watchdog);
+    v8::MaybeLocal<v8::Value> loadenv_ret =
+        node::LoadEnvironment(env, options.main_script);
 
     if (loadenv_ret.IsEmpty()) {
+      // The event loop is not run, its exit code would hide the exception
       result.exit_code = 1;
+      result.status = NODE_RUN_BOOTSTRAP_EXCEPTION;
+    } else {
+      result.exit_code = node::SpinEventLoop(env).FromMaybe(0);
+    }
+    set_env(instance, nullptr, nullptr);
+
+    {
+      std::lock_guard<std::mutex> guard(instance->mutex);
+      if (instance->terminated) {
+        result.status = NODE_RUN_TERMINATED;
+      }
+    }
+
+    if (state.out_of_memory) {
+      result.status = NODE_RUN_OUT_OF_MEMORY;
     }
 
-    set_env(env);
-    result.exit_code = node::SpinEventLoop(env).FromMaybe(0);
-    set_env(nullptr);
+    result.time_limit = watchdog.run_exceeded();
+    if (result.time_limit != NODE_TIME_LIMIT_NONE) {
+      result.status = NODE_RUN_TIME_LIMIT_EXCEEDED;
+    }
+
+    if (state.input != nullptr) {
+      state.input->Close();
+    }
+
+    if (state.uncaught_exception) {
+      if (result.status == NODE_RUN_OK) {
+        result.status = NODE_RUN_UNCAUGHT_EXCEPTION;
+      }
+
+      result.exception = {copy_string(state.exception_name),
+                          copy_string(state.exception_message),
+                          copy_string(state.exception_stack),
+                          copy_string(state.exception_cause)};
+    }
+
+    isolate->RemoveNearHeapLimitCallback(NearHeapLimit, 0);
+    if (options.heap_limit != 0) {
+      isolate->RemoveGCEpilogueCallback(CheckHeapLimit, &state);
+    }
   }
 
   node::Stop(env);
@@ -108,44 +1234,296 @@ node_run_result_t RunNodeInstance(node::MultiIsolatePlatform* platform,
 
 extern "C" {
 node_run_result_t node_run(node_options_t options) {
-  char** argv =
-      uv_setup_args(options.process_argc, (char**)options.process_argv);
-  std::vector<std::string> args(argv, argv + options.process_argc);
-  std::unique_ptr<node::InitializationResult> result =
-      node::InitializeOncePerProcess(
-          args,
-          {node::ProcessInitializationFlags::kNoInitializeV8,
-           node::ProcessInitializationFlags::kNoInitializeNodeV8Platform});
+  // Created first, so the input data is released on every path.
+  std::shared_ptr<InputReader> input;
+  if (options.input_callback != nullptr) {
+    input = std::make_shared<InputReader>(options.input_callback,
+                                          options.input_unread,
+                                          options.input_release,
+                                          options.input_data);
+  }
+  if (options.main_script == nullptr) {
+    return {1,
+            copy_string(std::string("No main script was passed")),
+            NODE_RUN_INIT_FAILED};
+  }
 
-  if (result->early_return() != 0) {
-    return {result->exit_code(), join_errors(result->errors())};
+  std::vector<std::string> args;
+  std::vector<std::string> exec_args;
+  std::shared_ptr<node::PerIsolateOptions> run_options;
+  int exit_code = 0;
+  char* error = nullptr;
+  node_run_status_t status = create_env_args(
+      options, &args, &exec_args, &run_options, &exit_code, &error);
+  if (status != NODE_RUN_OK) {
+    return {exit_code, error, status};
   }
 
-  std::unique_ptr<node::MultiIsolatePlatform> platform =
-      node::MultiIsolatePlatform::Create(4);
-  v8::V8::InitializePlatform(platform.get());
-  v8::V8::Initialize();
+  node::EmbedderSnapshotData::Pointer snapshot;
+  if (options.snapshot_data != nullptr) {
+    std::string snapshot_error;
+    snapshot = load_snapshot(
+        options.snapshot_data, options.snapshot_length, &snapshot_error);
+    if (!snapshot) {
+      return {1, copy_string(snapshot_error), NODE_RUN_INVALID_SNAPSHOT};
+    }
+  }
+
+  std::optional<std::vector<std::string>> env_vars;
+  if (options.env_vars != nullptr) {
+    env_vars = create_arg_vec(options.env_count, options.env_vars);
+  }
+
+  // Runs without a caller-provided instance can only be stopped by node_stop().
+  node_instance_t local_instance;
+  node_instance_t* instance =
+      options.instance != nullptr ? options.instance : &local_instance;
+
+  node_run_result_t result = RunNodeInstance(instance,
+                                             platform,
+                                             args,
+                                             exec_args,
+                                             run_options,
+                                             env_vars ? &*env_vars : nullptr,
+                                             std::move(input),
+                                             snapshot.get(),
+                                             options);
+  if (instance == &local_instance) {
+    wait_for_shutdowns(instance);
+  }
+  return result;
+}
 
-  node_run_result_t ret =
-      RunNodeInstance(platform.get(),
-                      result->args(),
-                      result->exec_args(),
-                      napi_addon_register_func(options.napi_reg_func));
+node_snapshot_t node_create_snapshot(node_options_t options) {
+  node_snapshot_t result{nullptr, 0, 0, nullptr};
+  std::vector<std::string> args;
+  std::vector<std::string> exec_args;
+  std::shared_ptr<node::PerIsolateOptions> run_options;
+  if (create_env_args(options,
+                      &args,
+                      &exec_args,
+                      &run_options,
+                      &result.exit_code,
+                      &result.error) != NODE_RUN_OK) {
+    return result;
+  }
+
+  std::vector<std::string> errors;
+  std::unique_ptr<node::CommonEnvironmentSetup> setup =
+      node::CommonEnvironmentSetup::CreateForSnapshotting(
+          platform, &errors, args, exec_args);
+  if (!setup) {
+    result.exit_code = 1;
+    result.error = join_errors(errors);
+    return result;
+  }
+  apply_run_options(setup.get(), run_options);
+
+  v8::Isolate* isolate = setup->isolate();
+  node::Environment* env = setup->env();
+  // The default handler would exit the process.
+  node::SetProcessExitHandler(env, [&](node::Environment* env, int exit_code) {
+    result.exit_code = exit_code;
+    node::Stop(env);
+  });
+
+  {
+    v8::Locker locker(isolate);
+    v8::Isolate::Scope isolate_scope(isolate);
+    v8::HandleScope handle_scope(isolate);
+    v8::Context::Scope context_scope(setup->context());
+
+    if (options.env_vars != nullptr) {
+      env->set_env_vars(create_env_vars(
+          isolate, create_arg_vec(options.env_count, options.env_vars)));
+    }
+
+    v8::MaybeLocal<v8::Value> loadenv_ret = node::LoadEnvironment(
+        env, options.main_script != nullptr ? options.main_script : "");
+    if (loadenv_ret.IsEmpty()) {
+      result.exit_code = 1;
+      result.error = copy_string(
+          std::string("An exception was thrown while running the snapshot "
+                      "script"));
+      return result;
+    }
+
+    int exit_code = node::SpinEventLoop(env).FromMaybe(1);
+    if (result.exit_code == 0) {
+      result.exit_code = exit_code;
+    }
+    if (result.exit_code != 0) {
+      result.error = copy_string(
+          std::string("The snapshot script exited with a non-zero exit code"));
+      return result;
+    }
+
+    node::EmbedderSnapshotData::Pointer snapshot = setup->CreateSnapshot();
+    if (!snapshot) {
+      result.exit_code = 1;
+      result.error = copy_string(std::string("Failed to create the snapshot"));
+      return result;
+    }
+
+    std::string data = snapshot_header();
+    std::vector<char> blob = snapshot->ToBlob();
+    data.append(blob.begin(), blob.end());
+    result.data = static_cast<char*>(malloc(data.size()));
+    memcpy(result.data, data.data(), data.size());
+    result.length = data.size();
+  }
+
+  return result;
+}
 
-  v8::V8::Dispose();
-  v8::V8::DisposePlatform();
+node_check_options_result_t node_check_options(int argc,
+                                               const char* const* argv) {
+  std::vector<std::string> args = create_arg_vec(argc, argv);
+  args.insert(args.begin(), "node");
+  std::vector<std::string> exec_args;
+  std::vector<std::string> errors;
+  std::vector<std::string> v8_args;
+  parse_options(&args, &exec_args, &errors, &v8_args);
+
+  node_check_options_result_t result{nullptr, nullptr};
+  if (!errors.empty()) {
+    result.error = join_errors(errors);
+  }
+  if (!v8_args.empty()) {
+    result.v8_args = join_errors(v8_args);
+  }
+  return result;
+}
 
-  node::TearDownOncePerProcess();
+char* node_check_snapshot(const char* data, size_t length) {
+  std::string error;
+  if (!check_snapshot(data, length, nullptr, &error)) {
+    return copy_string(error);
+  }
 
-  return ret;
+  return nullptr;
 }
 
 int node_stop() {
-  std::lock_guard<std::mutex> guard(env_mutex);
-  if (env_ptr == nullptr) {
+  std::lock_guard<std::mutex> guard(running_mutex);
+  if (running_instances.empty()) {
     return -1;
   }
 
-  return node::Stop(env_ptr);
+  int result = 0;
+  for (node_instance_t* instance : running_instances) {
+    int code = node_instance_stop(instance);
+    if (result == 0) {
+      result = code;
+    }
+  }
+
+  return result;
+}
+
+int node_terminate() {
+  std::lock_guard<std::mutex> guard(running_mutex);
+  if (running_instances.empty()) {
+    return -1;
+  }
+
+  int result = 0;
+  for (node_instance_t* instance : running_instances) {
+    int code = node_instance_terminate(instance);
+    if (result == 0) {
+      result = code;
+    }
+  }
+
+  return result;
+}
+
+int node_shutdown(int timeout_ms) {
+  auto deadline = std::chrono::steady_clock::now() +
+                  std::chrono::milliseconds(timeout_ms);
+  // Counted as used, so they are not destroyed while the runs are waited for
+  // without holding the lock.
+  std::vector<node_instance_t*> instances;
+  {
+    std::lock_guard<std::mutex> guard(running_mutex);
+    if (running_instances.empty()) {
+      return -1;
+    }
+    instances.assign(running_instances.begin(), running_instances.end());
+    for (node_instance_t* instance : instances) {
+      ++instance->shutdowns;
+    }
+  }
+
+  // Runs that end on their own meanwhile count as graceful.
+  int result = 0;
+  for (node_instance_t* instance : instances) {
+    if (shutdown_instance(instance, deadline) == 1) {
+      result = 1;
+    }
+  }
+
+  {
+    std::lock_guard<std::mutex> guard(running_mutex);
+    for (node_instance_t* instance : instances) {
+      --instance->shutdowns;
+    }
+  }
+  shutdowns_done.notify_all();
+  return result;
+}
+
+node_instance_t* node_instance_create() {
+  return new node_instance_t();
+}
+
+void node_instance_destroy(node_instance_t* instance) {
+  wait_for_shutdowns(instance);
+  delete instance;
+}
+
+int node_instance_stop(node_instance_t* instance) {
+  std::lock_guard<std::mutex> guard(instance->mutex);
+  if (instance->env == nullptr) {
+    return -1;
+  }
+
+  return node::Stop(instance->env);
+}
+
+int node_instance_terminate(node_instance_t* instance) {
+  std::lock_guard<std::mutex> guard(instance->mutex);
+  if (instance->env == nullptr) {
+    return -1;
+  }
+
+  return terminate_locked(instance);
+}
+
+int node_instance_shutdown(node_instance_t* instance, int timeout_ms) {
+  return shutdown_instance(instance,
+                           std::chrono::steady_clock::now() +
+                               std::chrono::milliseconds(timeout_ms));
+}
+
+int node_instance_begin_time_limit(node_instance_t* instance,
+                                   uint64_t wall_time_ms,
+                                   uint64_t cpu_time_ms) {
+  std::lock_guard<std::mutex> guard(instance->mutex);
+  if (instance->watchdog == nullptr) {
+    return -1;
+  }
+
+  instance->watchdog->Begin(wall_time_ms, cpu_time_ms);
+  return 0;
+}
+
+node_time_limit_t node_instance_end_time_limit(node_instance_t* instance) {
+  std::lock_guard<std::mutex> guard(instance->mutex);
+  if (instance->watchdog == nullptr) {
+    return NODE_TIME_LIMIT_NONE;
+  }
+
+  return instance->watchdog->End();
 }
 }
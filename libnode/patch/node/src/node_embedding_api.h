@@ -1,26 +1,326 @@
 #ifndef NODE_EMBEDDING_API_H
 #define NODE_EMBEDDING_API_H
 
+#include <stddef.h>
+#include <stdint.h>
+
+// Incremented with every change to this API. The libnode release built from
+// this header is tagged with it, see libnode/scripts/config.py.
+#define NODE_EMBEDDING_API_VERSION 1
+
 #ifdef __cplusplus
 extern "C" {
 #endif
 
+// Identifies a Node.js environment, so it can be stopped independently of
+// other environments running in the same process.
+typedef struct node_instance_s node_instance_t;
+
+// Receives data written by JavaScript to stdout (fd 1) or stderr (fd 2).
+// Called on the event loop thread, in the order of the writes. Returns 0 on
+// success.
+typedef int (*node_output_callback_t)(void* data,
+                                      int fd,
+                                      const char* chunk,
+                                      size_t length);
+
+// A call of a console method, such as console.log.
 typedef struct {
+  const char* method;  // the name of the method, such as "log" or "warn"
+  // The arguments formatted like the console prints them. Not null-terminated.
+  const char* message;
+  size_t message_length;
+  // Each argument, inspected like the console prints it.
+  int argc;
+  const char* const* argv;
+} node_console_message_t;
+
+// Receives console calls on the event loop thread.
+typedef void (*node_console_callback_t)(void* data,
+                                        const node_console_message_t* message);
+
+// Reads up to `length` bytes of input for process.stdin into `buffer`.
+// Called on a separate thread, one read at a time, and may block. Returns the
+// number of bytes read, 0 at the end of the input, or -1 on error.
+typedef int (*node_input_callback_t)(void* data, char* buffer, size_t length);
+
+// Returns a chunk that was read by the input callback, but not passed to
+// JavaScript because the run ended. Called before the input is released, so
+// the next run using the same data can return the chunk first.
+typedef void (*node_input_unread_t)(void* data,
+                                    const char* chunk,
+                                    size_t length);
+
+// Releases the data of the input callback once it is no longer called. This
+// may happen after node_run has returned if the input callback blocks.
+typedef void (*node_input_release_t)(void* data);
+
+// Called on the event loop thread when the JavaScript heap of a run has
+// reached `limit` bytes, with `used` bytes in use. Returns a limit greater
+// than `limit` to let the run continue, or 0 to stop it.
+typedef size_t (*node_heap_limit_callback_t)(void* data,
+                                             size_t limit,
+                                             size_t used);
+
+// How a module resolved by a node_resolve_callback_t is loaded.
+typedef enum {
+  NODE_RESOLVE_DEFAULT = 0,  // resolved by Node.js as usual, without a value
+  NODE_RESOLVE_FILE,         // the value is the path of the file to load
+  NODE_RESOLVE_COMMONJS,     // the value is the source of a CommonJS module
+  NODE_RESOLVE_MODULE,       // the value is the source of an ES module
+  NODE_RESOLVE_ERROR,        // the value is the message of the error to throw
+} node_resolve_type_t;
+
+// Passes the value of a resolved module to `target`, which copies it.
+typedef void (*node_resolve_value_t)(void* target,
+                                     const char* value,
+                                     size_t length);
+
+// Resolves a module specifier passed to `require` (`is_import` is 0) or
+// `import` (`is_import` is 1) on the event loop thread. `referrer` is the
+// filename of the module that loads it, or its URL if it is not a file, or null.
+// Calls `set_value(target, ...)` once unless it returns NODE_RESOLVE_DEFAULT.
+typedef node_resolve_type_t (*node_resolve_callback_t)(
+    void* data,
+    const char* specifier,
+    const char* referrer,
+    int is_import,
+    node_resolve_value_t set_value,
+    void* target);
+
+// Initializes a native module, see node_options_t.modules. `env` is the
+// napi_env, `exports` the napi_value of the exports of the module. Returns the
+// napi_value of the exports, or null to use `exports`.
+typedef void* (*node_module_init_t)(void* data, void* env, void* exports);
+
+// A native module that JavaScript loads with process._linkedBinding(name).
+typedef struct {
+  const char* name;
+  node_module_init_t init;
+  void* data;  // passed to init
+} node_module_t;
+
+// A time limit of a run or of a call, see node_options_t.time_limit_ms.
+typedef enum {
+  NODE_TIME_LIMIT_NONE = 0,
+  NODE_TIME_LIMIT_WALL,  // wall-clock time
+  NODE_TIME_LIMIT_CPU,   // CPU time of the event loop thread
+} node_time_limit_t;
+
+typedef struct {
+  // Parsed by Node.js. Node.js options are removed and the remaining arguments
+  // become process.argv.
   int process_argc;
   const char* const* process_argv;
-  void* napi_reg_func;  // napi_addon_register_func
+  // Node.js and V8 options. They become process.execArgv. Per-process and V8
+  // options are applied by the first successful run in a process, later runs
+  // only apply the options of their environment. A later run fails with
+  // NODE_RUN_INVALID_OPTIONS if its V8 options differ from the first run,
+  // other per-process options are ignored.
+  int exec_argc;
+  const char* const* exec_argv;
+  // Appended to process.argv without being parsed.
+  int script_argc;
+  const char* const* script_argv;
+  void* napi_reg_func;        // napi_addon_register_func
+  node_instance_t* instance;  // optional, must outlive the call to node_run
+  // Optional. Native modules registered as linked bindings in addition to
+  // `__embedder_mod`, each under its name. Each module is initialized the
+  // first time it is loaded. Only needs to be valid during the call to
+  // node_run.
+  int module_count;
+  const node_module_t* modules;
+  // Source passed to node::LoadEnvironment, required. It must load the linked
+  // binding `__embedder_mod`.
+  // The linked binding `__embedder_internal` exports
+  // `reportUncaughtException(name, message, stack, cause)`, which the script
+  // calls when an exception is not handled, so the run reports
+  // NODE_RUN_UNCAUGHT_EXCEPTION and the details of the exception.
+  const char* main_script;
+  // Environment variables as `NAME=value` strings. They replace the process
+  // environment in process.env. Uses the process environment if null.
+  int env_count;
+  const char* const* env_vars;
+  // Optional. If set, `__embedder_internal` also exports
+  // `writeOutput(fd, buffer)`, which the main script uses to redirect
+  // process.stdout and process.stderr to the callback.
+  node_output_callback_t output_callback;
+  void* output_data;  // passed to output_callback
+  // Optional. If set, `__embedder_internal` also exports
+  // `readInput(callback)`, which the main script uses to read process.stdin
+  // from the callback. `input_release` is always called, even if the run
+  // fails to start.
+  node_input_callback_t input_callback;
+  node_input_unread_t input_unread;    // optional
+  node_input_release_t input_release;  // optional
+  void* input_data;  // passed to input_callback and input_release
+  // Optional. If set, `__embedder_internal` also exports
+  // `logConsole(method, message, args)`, which the main script uses to pass
+  // console calls to the callback instead of printing them.
+  node_console_callback_t console_callback;
+  void* console_data;  // passed to console_callback
+  // Optional. If set, `__embedder_internal` also exports
+  // `resolveModule(specifier, referrer, isImport)`, which the main script uses
+  // to resolve `require` and `import` with the callback. It returns undefined,
+  // or the type and the value of the resolved module as an array, and throws
+  // for NODE_RESOLVE_ERROR.
+  node_resolve_callback_t resolve_callback;
+  void* resolve_data;  // passed to resolve_callback
+  // Optional. The number of bytes the JavaScript heap may use, checked after
+  // each garbage collection. The limit of V8 itself applies even if it is 0.
+  // Once either limit is reached, the run is stopped with
+  // NODE_RUN_OUT_OF_MEMORY instead of aborting the process, unless
+  // heap_limit_callback raises the limit.
+  size_t heap_limit;
+  node_heap_limit_callback_t heap_limit_callback;  // optional
+  void* heap_limit_data;  // passed to heap_limit_callback
+  // Optional. The wall-clock and CPU time in milliseconds the run may take,
+  // measured from its start. The CPU time is the one of the event loop thread.
+  // Once either is exceeded, the run is stopped with
+  // NODE_RUN_TIME_LIMIT_EXCEEDED, even if JavaScript is running.
+  uint64_t time_limit_ms;
+  uint64_t cpu_time_limit_ms;
+  // Optional. A snapshot created by node_create_snapshot, which the
+  // environment starts from instead of bootstrapping Node.js. The main script
+  // still runs. Only needs to be valid during the call to node_run.
+  const char* snapshot_data;
+  size_t snapshot_length;
 } node_options_t;
 
+typedef enum {
+  NODE_RUN_OK = 0,
+  // Node.js or the environment could not be initialized.
+  NODE_RUN_INIT_FAILED,
+  // The options are invalid, or set V8 options that differ from the first
+  // run, see node_options_t.exec_argv.
+  NODE_RUN_INVALID_OPTIONS,
+  // An exception was thrown while running the main script.
+  NODE_RUN_BOOTSTRAP_EXCEPTION,
+  // An exception was not handled after the main script has run.
+  NODE_RUN_UNCAUGHT_EXCEPTION,
+  // The environment was stopped by node_terminate or node_instance_terminate.
+  NODE_RUN_TERMINATED,
+  // The JavaScript heap reached its limit, see node_options_t.heap_limit.
+  NODE_RUN_OUT_OF_MEMORY,
+  // The run exceeded a time limit, see node_run_result_t.time_limit.
+  NODE_RUN_TIME_LIMIT_EXCEEDED,
+  // The snapshot can't be used with this build, see node_check_snapshot.
+  NODE_RUN_INVALID_SNAPSHOT,
+} node_run_status_t;
+
+// An exception that was not handled by JavaScript. The strings are
+// null-terminated, or null if the exception has no such property. Caller is
+// responsible for calling free() on them.
+typedef struct {
+  char* name;
+  char* message;
+  char* stack;
+  char* cause;  // the inspected `cause` property
+} node_exception_t;
+
 typedef struct {
   int exit_code;
   char*
       error;  // null-terminated. Caller is responsible for calling free() on it
+  node_run_status_t status;
+  // Set if the main script reported an uncaught exception, all null otherwise.
+  node_exception_t exception;
+  // The time limit that was exceeded, if the status is
+  // NODE_RUN_TIME_LIMIT_EXCEEDED.
+  node_time_limit_t time_limit;
 } node_run_result_t;
 
+// Runs a Node.js environment and blocks until its event loop stops.
+// May be called again after a previous run has returned, and from several
+// threads at once. The process-wide initialization only happens on the
+// first call, later calls can't change the Node.js options.
 node_run_result_t node_run(node_options_t);
 
+typedef struct {
+  // The snapshot, or null if it could not be created. Caller is responsible
+  // for calling free() on it.
+  char* data;
+  size_t length;
+  int exit_code;
+  char* error;  // null-terminated. Caller is responsible for calling free()
+} node_snapshot_t;
+
+// Runs the main script in an environment created for snapshotting until its
+// event loop stops, then serializes the state of the environment. The script
+// is run as is, without the embedder bindings, and its `require` only loads
+// built-in modules. Only the arguments, environment variables and main script
+// of the options are used. Initializes the process like node_run.
+node_snapshot_t node_create_snapshot(node_options_t);
+
+// Checks that a snapshot was created by this build of Node.js, for the same
+// platform. Returns null if it was, or the reason why it can't be used
+// otherwise. Caller is responsible for calling free() on the result.
+char* node_check_snapshot(const char* data, size_t length);
+
+typedef struct {
+  // The errors of Node.js, separated by newlines. NULL if the options are
+  // valid.
+  char* error;
+  // The options that Node.js passes to V8, separated by newlines. NULL if
+  // there are none.
+  char* v8_args;
+} node_check_options_result_t;
+
+// Parses Node.js options like node_run, without applying them to the process.
+// Options that Node.js does not know are returned as V8 options, which V8 only
+// checks when the process is initialized. Caller is responsible for calling
+// free() on the strings of the result.
+node_check_options_result_t node_check_options(int argc,
+                                               const char* const* argv);
+
+// Stops all running environments. Returns -1 if none is running.
 int node_stop();
 
+// Stops all running environments like node_stop, but the runs report
+// NODE_RUN_TERMINATED. Returns -1 if none is running.
+int node_terminate();
+
+// Shuts down all running environments like node_instance_shutdown, with a
+// deadline shared by all of them. Returns 0 if all of them stopped in time, 1
+// if any was terminated and -1 if none is running.
+int node_shutdown(int timeout_ms);
+
+node_instance_t* node_instance_create();
+
+// The instance must not be running when it is destroyed. Waits for
+// node_shutdown calls that still use it.
+void node_instance_destroy(node_instance_t*);
+
+// Stops the environment running on the instance. This also interrupts the
+// JavaScript that is currently executing, even if it never yields to the event
+// loop. Returns -1 if the instance is not running.
+int node_instance_stop(node_instance_t*);
+
+// Stops the environment running on the instance like node_instance_stop, but
+// the run reports NODE_RUN_TERMINATED. Returns -1 if the instance is not
+// running. May be called from any thread.
+int node_instance_terminate(node_instance_t*);
+
+// Emits the `shutdown` event on `process` in the environment running on the
+// instance, and blocks until its event loop has no more work to do, which also
+// emits `beforeExit` and `exit`. If the run has not ended after `timeout_ms`,
+// it is terminated like node_instance_terminate. Returns 0 if the run ended in
+// time, 1 if it was terminated and -1 if the instance is not running.
+int node_instance_shutdown(node_instance_t*, int timeout_ms);
+
+// Limits the time of the JavaScript called on the event loop thread of the
+// instance until node_instance_end_time_limit is called. Once a limit is
+// exceeded, the JavaScript is terminated, but the environment keeps running.
+// A limit of 0 is ignored. Both functions must be called on the event loop
+// thread. Returns -1 if the instance is not running.
+int node_instance_begin_time_limit(node_instance_t*,
+                                   uint64_t wall_time_ms,
+                                   uint64_t cpu_time_ms);
+
+// Ends the time limits set by node_instance_begin_time_limit, and returns the
+// limit that was exceeded, if any. JavaScript can run again afterwards.
+node_time_limit_t node_instance_end_time_limit(node_instance_t*);
+
 #ifdef __cplusplus
 }
 #endif
@@ -6,7 +6,7 @@ import sys
 from . import config
 
 zipBasename = 'libnode-{}-{}-{}{}'.format(
-    config.nodeVersion,
+    config.libnodeVersion,
     sys.platform,
     config.arch,
     config.zipBasenameSuffix
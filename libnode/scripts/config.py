@@ -1,6 +1,7 @@
 import sys
 
 import os
+import re
 
 arch_triple_map = {
 	"x64": "x86_64",
@@ -28,6 +29,19 @@ if sys.platform == 'darwin':
 	os.environ["MACOSX_DEPLOYMENT_TARGET"] = "10.13"
 
 nodeVersion = os.environ['LIBNODE_NODE_VERSION']
+
+headerPath = os.path.realpath(
+	os.path.join(
+		os.path.dirname(__file__),
+		"..", "patch", "node", "src", "node_embedding_api.h"
+	)
+)
+with open(headerPath) as header:
+	apiVersion = re.search(r'#define NODE_EMBEDDING_API_VERSION (\d+)', header.read()).group(1)
+
+# Releases are versioned by the Node.js version and the embedding API,
+# so a changed API is never linked against an older build
+libnodeVersion = '{}-api{}'.format(nodeVersion, apiVersion)
 configFlags = (os.environ.get('LIBNODE_CONFIG_FLAGS') or '').split()
 
 arch = os.environ.get('LIBNODE_ARCH') or "x64"  # x64, arm64, x86
@@ -3,6 +3,7 @@ import os
 import shutil
 import subprocess
 import glob
+import re
 
 from . import config
 
@@ -17,18 +18,38 @@ shutil.rmtree(resultFolder, ignore_errors=True)
 
 os.mkdir(resultFolder)
 
-header_path = os.path.realpath(
-    os.path.join(
-        os.path.dirname(__file__),
-        "..", "patch", "node", "src", "node_embedding_api.h"
-    )
-)
 subprocess.check_call([
-    "bindgen", "--allowlist-function", "^node_.*", header_path,
+    "bindgen", config.headerPath,
+    "--allowlist-function", "^node_.*",
+    "--allowlist-var", "^NODE_.*",
     "--output", os.path.join(resultFolder, "sys.rs"),
     "--", "-target", config.target_triple
 ])
 
+# V8 does not offer a way to check flags without setting them, so the flags
+# this build knows are listed for the crate
+nodeBinary = os.path.join(
+    nodeSrcFolder, 'out', 'Release',
+    'node.exe' if sys.platform == 'win32' else 'node'
+)
+v8Options = subprocess.check_output([nodeBinary, '--v8-options'], text=True)
+v8Flags = sorted(set(re.findall(r'^  --([\w-]+)', v8Options, re.MULTILINE)))
+
+# The bindings keep the C names, so their lints are allowed here rather
+# than in the crate that includes them
+sysPath = os.path.join(resultFolder, "sys.rs")
+with open(sysPath) as sysFile:
+    bindings = sysFile.read()
+with open(sysPath, 'w') as sysFile:
+    sysFile.write(
+        '#[allow(dead_code, non_camel_case_types, non_upper_case_globals)]\n'
+        'mod bindings {\n' + bindings + '\n'
+        'pub const V8_FLAGS: &[&str] = &[\n' +
+        ''.join('    "{}",\n'.format(flag) for flag in v8Flags) +
+        '];\n}\n\n'
+        'pub use bindings::*;\n'
+    )
+
 os.mkdir(libFolder)
 
 def filterLibFile(filename):
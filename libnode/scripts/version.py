@@ -0,0 +1,5 @@
+from . import config
+
+assert __name__ == "__main__"
+
+print(config.libnodeVersion)
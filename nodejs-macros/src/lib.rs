@@ -0,0 +1,221 @@
+//! Macros of the `nodejs` crate. Use them through the `nodejs` crate, which re-exports them.
+
+use std::path::{Path, PathBuf};
+
+use proc_macro::{Delimiter, Group, Literal, Span, TokenStream, TokenTree};
+
+/// The format of a bundle, read by `nodejs::bundle::Bundle`.
+const MAGIC: &[u8; 8] = b"NODEJSB\x01";
+const FLAG_COMPRESSED: u8 = 1;
+
+/// Embeds a directory of JavaScript files, including its `node_modules`, into the binary
+/// and evaluates to a `nodejs::bundle::Bundle`.
+///
+/// The path is resolved against the directory of the `Cargo.toml` of the crate.
+/// Adding `compress` deflates the files:
+///
+/// ```ignore
+/// static APP: nodejs::bundle::Bundle = nodejs::include_bundle!("app", compress);
+/// ```
+///
+/// The crate is rebuilt if one of the embedded files changes,
+/// but not if files are added to the directory.
+#[proc_macro]
+pub fn include_bundle(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+type Error = (String, Span);
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let (dir, compress) = parse_input(input)?;
+
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let root = manifest_dir.join(&dir);
+    if !root.is_dir() {
+        return Err((
+            format!("The bundle directory {} does not exist", root.display()),
+            Span::call_site(),
+        ));
+    }
+
+    let mut files = Vec::new();
+    collect_files(&root, &root, &mut files).map_err(|e| (e, Span::call_site()))?;
+
+    let mut payload = Vec::new();
+    for (name, path) in &files {
+        let data = std::fs::read(path).map_err(|e| {
+            (
+                format!("Failed to read {}: {e}", path.display()),
+                Span::call_site(),
+            )
+        })?;
+        payload.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        payload.extend_from_slice(&data);
+    }
+
+    let mut bundle = MAGIC.to_vec();
+    if compress {
+        bundle.push(FLAG_COMPRESSED);
+        bundle.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&payload, 6));
+    } else {
+        bundle.push(0);
+        bundle.extend_from_slice(&payload);
+    }
+
+    // Unused includes, so cargo tracks the files
+    let mut body = String::new();
+    for (_, path) in &files {
+        body.push_str(&format!(
+            "const _: &[u8] = include_bytes!({:?});\n",
+            path.to_string_lossy()
+        ));
+    }
+    let mut body: TokenStream = body.parse().map_err(|_| {
+        (
+            "Failed to track the bundle files".to_string(),
+            Span::call_site(),
+        )
+    })?;
+    body.extend([TokenTree::Literal(Literal::byte_string(&bundle))]);
+
+    let mut output: TokenStream = "::nodejs::bundle::Bundle::from_static"
+        .parse()
+        .expect("valid path");
+    let block = TokenTree::Group(Group::new(Delimiter::Brace, body));
+    output.extend([TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        block.into(),
+    ))]);
+    Ok(output)
+}
+
+/// Parses `"path"` or `"path", compress`.
+fn parse_input(input: TokenStream) -> Result<(String, bool), Error> {
+    let mut tokens = input.into_iter();
+    let dir = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => parse_string(&literal)
+            .ok_or_else(|| ("Expected a string literal".to_string(), literal.span()))?,
+        Some(token) => return Err(("Expected a string literal".to_string(), token.span())),
+        None => {
+            return Err((
+                "Expected the path of the bundle directory".to_string(),
+                Span::call_site(),
+            ))
+        }
+    };
+
+    let mut compress = false;
+    match tokens.next() {
+        None => {}
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ',' => match tokens.next() {
+            None => {}
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "compress" => {
+                compress = true;
+                if let Some(TokenTree::Punct(punct)) = tokens.next() {
+                    if punct.as_char() != ',' {
+                        return Err(("Unexpected token".to_string(), punct.span()));
+                    }
+                }
+            }
+            Some(token) => return Err(("Expected `compress`".to_string(), token.span())),
+        },
+        Some(token) => return Err(("Expected `,`".to_string(), token.span())),
+    }
+
+    if let Some(token) = tokens.next() {
+        return Err(("Unexpected token".to_string(), token.span()));
+    }
+
+    Ok((dir, compress))
+}
+
+/// Returns the value of a string literal, or `None` if it is another literal.
+fn parse_string(literal: &Literal) -> Option<String> {
+    let literal = literal.to_string();
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let raw = &raw[hashes..raw.len() - hashes];
+        return Some(raw.strip_prefix('"')?.strip_suffix('"')?.to_string());
+    }
+
+    let quoted = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            '0' => value.push('\0'),
+            c @ ('\\' | '"' | '\'') => value.push(c),
+            _ => return None,
+        }
+    }
+
+    Some(value)
+}
+
+/// Collects the files below `dir` with their paths relative to `root`, joined by `/`.
+/// Symbolic links are followed.
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), String> {
+    let read_error = |e: std::io::Error| format!("Failed to read {}: {e}", dir.display());
+    let mut entries = std::fs::read_dir(dir)
+        .map_err(read_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+    // Sorted, so the bundle does not depend on the order of the file system
+    entries.sort();
+
+    for path in entries {
+        let metadata = std::fs::metadata(&path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        if metadata.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+
+        let name = path
+            .strip_prefix(root)
+            .map_err(|e| e.to_string())?
+            .components()
+            .map(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .ok_or_else(|| format!("The path {} is not valid Unicode", path.display()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join("/");
+        files.push((name, path));
+    }
+
+    Ok(())
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut output: TokenStream = "::core::compile_error!".parse().expect("valid path");
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    output.extend([TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        TokenTree::Literal(literal).into(),
+    ))]);
+    output
+}
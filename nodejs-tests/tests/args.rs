@@ -0,0 +1,224 @@
+use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
+use nodejs::options::{Deprecations, NodeOptions, OptionsError};
+
+#[chazi::test(check_reach)]
+fn test_exec_and_script_args() {
+    let mut exec_args = Vec::<String>::new();
+    let mut script_args = Vec::<String>::new();
+    let res = nodejs::run_napi(
+        |env| {
+            exec_args = env.run_script("process.execArgv")?;
+            script_args = env.run_script("process.argv.slice(1)")?;
+            Ok(())
+        },
+        Some(
+            NodeArgs::new()
+                .exec_args(["--no-warnings", "--stack-size=2000"])
+                .script_args(["--port", "8080", "--max-old-space-size=1"]),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(exec_args, vec!["--no-warnings", "--stack-size=2000"]);
+    assert_eq!(
+        script_args,
+        vec!["--port", "8080", "--max-old-space-size=1"]
+    );
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_invalid_exec_arg() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().exec_args(["--not-a-node-option"])),
+    );
+
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidArgument);
+    let source = std::error::Error::source(&err)
+        .and_then(|source| source.downcast_ref::<OptionsError>())
+        .unwrap();
+    assert_eq!(
+        source,
+        &OptionsError::UnknownOption("--not-a-node-option".to_string())
+    );
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_exec_args_change() {
+    let no_deprecation = |args: NodeArgs| {
+        let mut no_deprecation = false;
+        let res = nodejs::run_napi(
+            |env| {
+                no_deprecation = env.run_script("process.noDeprecation === true")?;
+                Ok(())
+            },
+            Some(args),
+        );
+        assert!(res.is_ok(), "{}", res.err().unwrap());
+        no_deprecation
+    };
+
+    assert!(!no_deprecation(
+        NodeArgs::new().exec_args(["--no-warnings"])
+    ));
+    assert!(no_deprecation(
+        NodeArgs::new().exec_args(["--no-deprecation"])
+    ));
+    assert!(!no_deprecation(NodeArgs::new()));
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_v8_options_change() {
+    let run = |mib: u64| {
+        nodejs::run_napi(
+            |_| Ok(()),
+            Some(NodeArgs::new().options(NodeOptions::new().max_old_space_size(mib))),
+        )
+    };
+
+    let res = run(256);
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(run(512).err().unwrap().kind(), ErrorKind::InvalidArgument);
+    let res = run(256);
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_invalid_exec_arg_is_not_kept() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().exec_args(["--not-a-node-option"])),
+    );
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidArgument);
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().exec_args(["--no-warnings"])),
+    );
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_typed_options() {
+    let mut exec_args = Vec::<String>::new();
+    let res = nodejs::run_napi(
+        |env| {
+            exec_args = env.run_script("process.execArgv")?;
+            Ok(())
+        },
+        Some(
+            NodeArgs::new()
+                .options(
+                    NodeOptions::new()
+                        .max_old_space_size(512)
+                        .deprecations(Deprecations::Silent)
+                        .source_maps(true),
+                )
+                .exec_args(["--expose-gc"]),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(
+        exec_args,
+        vec![
+            "--max-old-space-size=512",
+            "--no-deprecation",
+            "--enable-source-maps",
+            "--expose-gc"
+        ]
+    );
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_invalid_options() {
+    assert_eq!(
+        NodeOptions::new().max_old_space_size(0).to_args(),
+        Err(OptionsError::InvalidValue {
+            option: "--max-old-space-size".to_string(),
+            reason: "must be greater than 0".to_string(),
+        })
+    );
+    assert_eq!(
+        NodeOptions::new().v8_flag("expose-gc").to_args(),
+        Err(OptionsError::InvalidOption("expose-gc".to_string()))
+    );
+    assert_eq!(
+        NodeOptions::new().v8_flag("--version").to_args(),
+        Err(OptionsError::UnsupportedOption("--version".to_string()))
+    );
+    assert_eq!(
+        NodeOptions::new()
+            .stack_size(1000)
+            .v8_flag("--stack_size=2000")
+            .to_args(),
+        Err(OptionsError::ConflictingOptions(
+            "--stack-size=1000".to_string(),
+            "--stack_size=2000".to_string()
+        ))
+    );
+    assert_eq!(
+        NodeOptions::new().v8_flag("--not-a-v8-flag").to_args(),
+        Err(OptionsError::UnknownOption("--not-a-v8-flag".to_string()))
+    );
+    assert!(matches!(
+        NodeOptions::new().v8_flag("--require").to_args(),
+        Err(OptionsError::Rejected(_))
+    ));
+    assert!(NodeOptions::new()
+        .v8_flag("--no-expose_gc")
+        .v8_flag("--max-lazy")
+        .to_args()
+        .is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_conflicting_exec_args() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .options(NodeOptions::new().deprecations(Deprecations::Throw))
+                .exec_args(["--no-deprecation"]),
+        ),
+    );
+
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidArgument);
+    let source = std::error::Error::source(&err)
+        .and_then(|source| source.downcast_ref::<OptionsError>())
+        .unwrap();
+    assert!(matches!(source, OptionsError::ConflictingOptions(..)));
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .options(NodeOptions::new().deprecations(Deprecations::Pending))
+                .exec_args(["--throw-deprecation"]),
+        ),
+    );
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_nul_in_args() {
+    let res = nodejs::run_napi(|_| Ok(()), Some(NodeArgs::new().script_args(["a\0b"])));
+
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidArgument);
+    assert!(std::error::Error::source(&err)
+        .unwrap()
+        .is::<std::ffi::NulError>());
+    chazi::reached::last()
+}
@@ -0,0 +1,101 @@
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::bundle::Bundle;
+use nodejs::error::ErrorKind;
+use nodejs::runtime::ModuleNamespace;
+use nodejs::Runtime;
+
+static BUNDLE: Bundle = nodejs::include_bundle!("tests/bundle");
+static COMPRESSED_BUNDLE: Bundle = nodejs::include_bundle!("tests/bundle", compress);
+
+/// Waits for the module on the current thread.
+fn import(runtime: &Runtime, specifier: &str) -> nodejs::Result<ModuleNamespace> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(runtime.import(specifier))
+}
+
+#[chazi::test(check_reach)]
+fn test_bundle_require_and_import() {
+    let runtime = Runtime::spawn(
+        NodeArgs::new()
+            .bundle(BUNDLE.clone())
+            .main_script(MainScript::File("index.js".into())),
+    )
+    .unwrap();
+
+    let greeting: String = runtime.eval("globalThis.greeting").unwrap();
+    assert_eq!(greeting, "Hello, bundle!");
+    let answer: i32 = runtime.eval("globalThis.answer").unwrap();
+    assert_eq!(answer, 42);
+
+    let module = import(&runtime, "./lib/message.mjs").unwrap();
+    let message: String = module.get("message").unwrap();
+    assert_eq!(message, "Hello, module!");
+
+    drop(module);
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_bundle_compressed() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bundle_compressed");
+    let _ = std::fs::remove_dir_all(&dir);
+    COMPRESSED_BUNDLE.extract_to(&dir).unwrap();
+
+    let package = std::fs::read_to_string(dir.join("node_modules/greeting/package.json")).unwrap();
+    assert!(package.contains("\"main\": \"index.js\""), "{package}");
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .bundle(COMPRESSED_BUNDLE.clone())
+                .main_script(MainScript::Source {
+                    source: "process.exitCode = require('./lib/answer').answer;".to_string(),
+                    filename: "main.js".to_string(),
+                }),
+        ),
+    );
+
+    assert_eq!(res.err().unwrap().code(), 42);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_bundle_from_bytes() {
+    let bundle = Bundle::from_bytes(COMPRESSED_BUNDLE.as_bytes()).unwrap();
+    assert_eq!(
+        bundle.extract().unwrap(),
+        COMPRESSED_BUNDLE.extract().unwrap()
+    );
+
+    let err = Bundle::from_bytes(b"not a bundle".to_vec()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidBundle);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_bundle_extract_replaces_incomplete_extraction() {
+    let dir = BUNDLE.extract().unwrap();
+    // Like an extraction that was interrupted
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/answer.js"), "exports.answer = 0;").unwrap();
+
+    assert_eq!(BUNDLE.extract().unwrap(), dir);
+    let answer = std::fs::read_to_string(dir.join("lib/answer.js")).unwrap();
+    assert!(answer.contains("42"), "{answer}");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.parent().unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+    chazi::reached::last()
+}
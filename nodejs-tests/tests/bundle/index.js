@@ -0,0 +1,5 @@
+const { greet } = require('greeting');
+const { answer } = require('./lib/answer');
+
+globalThis.greeting = greet('bundle');
+globalThis.answer = answer;
@@ -0,0 +1,3 @@
+import { greet } from 'greeting';
+
+export const message = greet('module');
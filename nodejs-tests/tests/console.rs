@@ -0,0 +1,124 @@
+use std::sync::{Arc, Mutex};
+
+use nodejs::args::NodeArgs;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+#[derive(Debug, Default)]
+struct RecordedEvent {
+    target: String,
+    level: Option<Level>,
+    fields: Vec<(String, String)>,
+}
+
+impl RecordedEvent {
+    fn field(&self, name: &str) -> &str {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+}
+
+impl Visit for RecordedEvent {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.fields
+            .push((field.name().to_string(), format!("{value:?}")));
+    }
+}
+
+/// Records the events emitted on the current thread, and panics on events of the level.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<RecordedEvent>>>, Option<Level>);
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if self.1 == Some(*event.metadata().level()) {
+            panic!("The subscriber failed");
+        }
+
+        let mut recorded = RecordedEvent {
+            target: event.metadata().target().to_string(),
+            level: Some(*event.metadata().level()),
+            fields: Vec::new(),
+        };
+        event.record(&mut recorded);
+        self.0.lock().unwrap().push(recorded);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[chazi::test(check_reach)]
+fn test_console_tracing() {
+    let recorder = Recorder::default();
+    let res = tracing::subscriber::with_default(recorder.clone(), || {
+        nodejs::run_napi(
+            |env| {
+                env.run_script::<_, napi::JsUnknown>(
+                    "console.log('answer: %d', 42, { ok: true }); \
+                     console.warn('careful'); \
+                     console.error(new Error('oops')); \
+                     console.debug('details')",
+                )?;
+                Ok(())
+            },
+            Some(NodeArgs::new().trace_console("test-runtime")),
+        )
+    });
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    let events = recorder.0.lock().unwrap();
+    let levels: Vec<_> = events.iter().map(|event| event.level.unwrap()).collect();
+    assert_eq!(
+        levels,
+        vec![Level::INFO, Level::WARN, Level::ERROR, Level::DEBUG]
+    );
+    assert!(events.iter().all(|event| event.target == "nodejs::console"));
+
+    let log = &events[0];
+    assert_eq!(log.field("message"), "answer: 42 { ok: true }");
+    assert_eq!(log.field("runtime"), "test-runtime");
+    assert_eq!(log.field("method"), "log");
+    assert_eq!(log.field("args"), r#"["answer: %d", "42", "{ ok: true }"]"#);
+    assert!(events[2].field("message").contains("Error: oops"));
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_console_tracing_panic() {
+    let recorder = Recorder(Default::default(), Some(Level::WARN));
+    let res = tracing::subscriber::with_default(recorder.clone(), || {
+        nodejs::run_napi(
+            |env| {
+                env.run_script::<_, napi::JsUnknown>(
+                    "console.warn('dropped'); console.log('kept')",
+                )?;
+                Ok(())
+            },
+            Some(NodeArgs::new().trace_console("test-runtime")),
+        )
+    });
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].field("message"), "kept");
+    chazi::reached::last()
+}
@@ -0,0 +1,69 @@
+use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
+
+#[chazi::test(check_reach)]
+fn test_env_overrides() {
+    std::env::set_var("NODEJS_TEST_INHERITED", "inherited");
+    std::env::set_var("NODEJS_TEST_REMOVED", "removed");
+
+    let mut values = Vec::<Option<String>>::new();
+    let res = nodejs::run_napi(
+        |env| {
+            values = env.run_script(
+                "['NODEJS_TEST_INHERITED', 'NODEJS_TEST_REMOVED', 'NODEJS_TEST_SET'] \
+                 .map((name) => process.env[name] ?? null)",
+            )?;
+            Ok(())
+        },
+        Some(
+            NodeArgs::new()
+                .env("NODEJS_TEST_SET", "set")
+                .env_remove("NODEJS_TEST_REMOVED"),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(
+        values,
+        vec![Some("inherited".to_string()), None, Some("set".to_string())]
+    );
+    assert!(std::env::var("NODEJS_TEST_SET").is_err());
+    assert_eq!(std::env::var("NODEJS_TEST_REMOVED").unwrap(), "removed");
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_env_clear() {
+    std::env::set_var("NODEJS_TEST_INHERITED", "inherited");
+
+    let mut names = Vec::<String>::new();
+    let res = nodejs::run_napi(
+        |env| {
+            names = env.run_script(
+                "process.env.NODEJS_TEST_WRITTEN = 'written'; Object.keys(process.env).sort()",
+            )?;
+            Ok(())
+        },
+        Some(
+            NodeArgs::new()
+                .envs([("NODEJS_TEST_A", "a"), ("NODEJS_TEST_B", "b")])
+                .env_clear(),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(
+        names,
+        vec!["NODEJS_TEST_A", "NODEJS_TEST_B", "NODEJS_TEST_WRITTEN"]
+    );
+    assert!(std::env::var("NODEJS_TEST_WRITTEN").is_err());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_invalid_env_name() {
+    let res = nodejs::run_napi(|_| Ok(()), Some(NodeArgs::new().env("A=B", "c")));
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidArgument);
+    chazi::reached::last()
+}
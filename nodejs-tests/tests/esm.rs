@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::runtime::ModuleNamespace;
+use nodejs::Runtime;
+
+/// Waits for the module on the current thread.
+fn import(runtime: &Runtime, specifier: &str) -> nodejs::Result<ModuleNamespace> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(runtime.import(specifier))
+}
+
+fn module_root(name: &str) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("answer.mjs"), "export const answer = 40;").unwrap();
+    root
+}
+
+#[chazi::test(check_reach)]
+fn test_esm_main_script_module() {
+    let runtime = Runtime::spawn(
+        NodeArgs::new()
+            .module_root(module_root("esm_main_script_module"))
+            .main_script(MainScript::Module {
+                source: "import { answer } from './answer.mjs'; \
+                         globalThis.answer = answer + 2; \
+                         globalThis.url = import.meta.url;"
+                    .to_string(),
+                filename: "main.mjs".to_string(),
+            }),
+    )
+    .unwrap();
+
+    let answer: i32 = runtime.eval("globalThis.answer").unwrap();
+    assert_eq!(answer, 42);
+    let url: String = runtime.eval("globalThis.url").unwrap();
+    assert!(
+        url.starts_with("file://") && url.ends_with("/main.mjs"),
+        "{url}"
+    );
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_esm_main_script_file() {
+    let root = module_root("esm_main_script_file");
+    std::fs::write(
+        root.join("app.mjs"),
+        "import { answer } from './answer.mjs'; process.exitCode = answer + 2;",
+    )
+    .unwrap();
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().main_script(MainScript::File(root.join("app.mjs")))),
+    );
+
+    assert!(res.is_err());
+    assert_eq!(res.err().unwrap().code(), 42);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_import() {
+    let runtime =
+        Runtime::spawn(NodeArgs::new().module_root(module_root("runtime_import"))).unwrap();
+
+    let module = import(&runtime, "./answer.mjs").unwrap();
+    let answer: i32 = module.get("answer").unwrap();
+    assert_eq!(answer, 40);
+
+    let export_count = module
+        .exec(|_, namespace| namespace.get_property_names()?.get_array_length())
+        .unwrap();
+    assert_eq!(export_count, 1);
+
+    let fs = import(&runtime, "node:fs").unwrap();
+    let read_file_type = fs
+        .exec(|_, namespace| {
+            namespace
+                .get_named_property_unchecked::<napi::JsUnknown>("readFileSync")?
+                .get_type()
+        })
+        .unwrap();
+    assert_eq!(read_file_type, napi::ValueType::Function);
+
+    drop(module);
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_import_error() {
+    let root = module_root("runtime_import_error");
+    std::fs::write(root.join("throws.mjs"), "throw new Error('oops');").unwrap();
+    let runtime = Runtime::spawn(NodeArgs::new().module_root(&root)).unwrap();
+
+    let err = import(&runtime, "./throws.mjs").err().unwrap();
+    assert!(err.message().contains("oops"), "{err}");
+    assert!(err.stack().unwrap().contains("throws.mjs"), "{err:?}");
+
+    let err = import(&runtime, "./missing.mjs").err().unwrap();
+    assert!(err.message().contains("missing.mjs"), "{err}");
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
@@ -0,0 +1,105 @@
+use nodejs::args::NodeArgs;
+use nodejs::global::GlobalValue;
+use nodejs::Runtime;
+use serde::{Deserialize, Serialize};
+
+#[chazi::test(check_reach)]
+fn test_globals() {
+    let runtime = Runtime::spawn(
+        NodeArgs::new()
+            .global("appName", "service")
+            .global("answer", 42)
+            .global("flags", vec![true, false])
+            .global("config", GlobalValue::Json(r#"{"port":8080}"#.to_string())),
+    )
+    .unwrap();
+
+    let app_name: String = runtime.eval("appName").unwrap();
+    assert_eq!(app_name, "service");
+    let answer: i32 = runtime.eval("answer").unwrap();
+    assert_eq!(answer, 42);
+    let flags: Vec<bool> = runtime.eval("flags").unwrap();
+    assert_eq!(flags, vec![true, false]);
+    let port: u32 = runtime.eval("config.port").unwrap();
+    assert_eq!(port, 8080);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_frozen_global() {
+    let config = GlobalValue::Object(vec![
+        ("name".to_string(), "service".into()),
+        ("ports".to_string(), vec![80, 443].into()),
+    ])
+    .frozen();
+    let runtime = Runtime::spawn(NodeArgs::new().global("config", config)).unwrap();
+
+    let frozen: bool = runtime
+        .eval("'use strict'; Object.isFrozen(config) && Object.isFrozen(config.ports)")
+        .unwrap();
+    assert!(frozen);
+
+    let res = runtime.eval::<(), _>("'use strict'; config.ports.push(8080)");
+    assert!(res.is_err());
+    let name: String = runtime
+        .eval("globalThis.config = null; config.name")
+        .unwrap();
+    assert_eq!(name, "service");
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_globals_visible_in_init() {
+    let mut answer = 0;
+    let res = nodejs::run_napi(
+        |env| {
+            let res: napi::JsNumber = env.run_script("answer")?;
+            answer = res.get_int32()?;
+            Ok(())
+        },
+        Some(NodeArgs::new().global("answer", 42)),
+    );
+
+    assert!(res.is_ok());
+    assert_eq!(answer, 42);
+    chazi::reached::last()
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    ports: Vec<u16>,
+    debug: bool,
+    limits: Option<Limits>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Limits {
+    connections: u32,
+}
+
+#[chazi::test(check_reach)]
+fn test_serialized_global() {
+    let config = Config {
+        name: "service \"ünïcode\"".to_string(),
+        ports: vec![80, 443],
+        debug: true,
+        limits: Some(Limits { connections: 100 }),
+    };
+    let runtime = Runtime::spawn(
+        NodeArgs::new().global("config", GlobalValue::from_serialize(&config).unwrap()),
+    )
+    .unwrap();
+
+    let json: String = runtime.eval("JSON.stringify(config)").unwrap();
+    assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+    let port: u16 = runtime.eval("config.ports[1]").unwrap();
+    assert_eq!(port, 443);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
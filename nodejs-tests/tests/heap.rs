@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::error::ErrorKind;
+
+const MB: usize = 1024 * 1024;
+
+fn allocate(count: &str) -> MainScript {
+    MainScript::Source {
+        source: format!(
+            "const objects = []; \
+             for (let i = 0; i < {count}; i++) objects.push({{ i, s: 'x' + i }});"
+        ),
+        filename: "main.js".to_string(),
+    }
+}
+
+#[chazi::test(check_reach)]
+fn test_heap_limit_out_of_memory() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(allocate("Infinity"))
+                .heap_limit(32 * MB),
+        ),
+    );
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::OutOfMemory);
+
+    // The process is still usable
+    let res = nodejs::run_napi(|_| Ok(()), None);
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_heap_limit_raised() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let handler_calls = calls.clone();
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(allocate("1e6"))
+                .heap_limit(32 * MB)
+                .on_heap_limit(move |limit, _used| {
+                    handler_calls.fetch_add(1, Ordering::SeqCst);
+                    Some(limit + 1024 * MB)
+                }),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert!(calls.load(Ordering::SeqCst) > 0);
+    chazi::reached::last()
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::error::ErrorKind;
+use nodejs::limits::TimeLimits;
+use nodejs::Runtime;
+
+fn endless_loop() -> MainScript {
+    MainScript::Source {
+        source: "for (;;) {}".to_string(),
+        filename: "main.js".to_string(),
+    }
+}
+
+#[chazi::test(check_reach)]
+fn test_run_wall_time_limit() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(endless_loop())
+                .time_limits(TimeLimits::new().wall_time(Duration::from_millis(500))),
+        ),
+    );
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::TimeLimitExceeded);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_run_cpu_time_limit() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(endless_loop())
+                .time_limits(TimeLimits::new().cpu_time(Duration::from_millis(500))),
+        ),
+    );
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::CpuTimeLimitExceeded);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_eval_time_limits() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let limits = TimeLimits::new().wall_time(Duration::from_millis(200));
+    let err = runtime
+        .eval_with_limits::<(), _>("for (;;) {}", limits)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::TimeLimitExceeded);
+
+    let limits = TimeLimits::new().cpu_time(Duration::from_millis(200));
+    let err = runtime
+        .eval_with_limits::<(), _>("for (;;) {}", limits)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::CpuTimeLimitExceeded);
+
+    // The runtime keeps running
+    let answer: i32 = runtime.eval_with_limits("40+2", limits).unwrap();
+    assert_eq!(answer, 42);
+    let answer: i32 = runtime.eval("40+2").unwrap();
+    assert_eq!(answer, 42);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
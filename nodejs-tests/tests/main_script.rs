@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::error::ErrorKind;
+use nodejs::stdio::OutputBuffer;
+use nodejs::Runtime;
+
+#[chazi::test(check_reach)]
+fn test_main_script_source() {
+    let runtime = Runtime::spawn(
+        NodeArgs::new()
+            .main_script(MainScript::Source {
+                source: "globalThis.mainFilename = __filename; \
+                     globalThis.isMain = require.main === module;"
+                    .to_string(),
+                filename: "virtual/main.js".to_string(),
+            })
+            .script_args(["--port"]),
+    )
+    .unwrap();
+
+    let filename: String = runtime.eval("globalThis.mainFilename").unwrap();
+    assert!(filename.ends_with("main.js"), "{filename}");
+    let argv: Vec<String> = runtime.eval("process.argv.slice(1)").unwrap();
+    assert_eq!(argv, vec![filename, "--port".to_string()]);
+    let is_main: bool = runtime.eval("globalThis.isMain").unwrap();
+    assert!(is_main);
+
+    let has_require: bool = runtime
+        .eval("typeof globalThis.require === 'function'")
+        .unwrap();
+    assert!(!has_require);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_main_script_file() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("main_script_file");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("dep.js"),
+        "module.exports = process.argv[1] === require.main.filename ? 40 : 0;",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("app.js"),
+        "process.exitCode = require('./dep') + 2;",
+    )
+    .unwrap();
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().main_script(MainScript::File(dir.join("app.js")))),
+    );
+
+    assert!(res.is_err());
+    assert_eq!(res.err().unwrap().code(), 42);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_main_script_builtin_bootstrap() {
+    let runtime = Runtime::spawn(
+        NodeArgs::new()
+            .main_script(MainScript::Source {
+                source: "globalThis.flag = embedVars['nön_ascıı'];".to_string(),
+                filename: "main.js".to_string(),
+            })
+            .builtin_bootstrap(true),
+    )
+    .unwrap();
+
+    let flag: String = runtime.eval("globalThis.flag").unwrap();
+    assert_eq!(flag, "🏳️‍🌈");
+    let has_require: bool = runtime
+        .eval("typeof globalThis.require === 'function'")
+        .unwrap();
+    assert!(has_require);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_main_script_throws() {
+    let stdout = OutputBuffer::new();
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(MainScript::Source {
+                    source: "setImmediate(() => process.stdout.write('ran')); \
+                         throw new Error('load failed');"
+                        .to_string(),
+                    filename: "main.js".to_string(),
+                })
+                .stdout(stdout.clone()),
+        ),
+    );
+
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BootstrapException);
+    assert_eq!(err.code(), 1);
+    // The event loop does not run after the exception
+    assert!(stdout.contents().is_empty());
+    chazi::reached::last()
+}
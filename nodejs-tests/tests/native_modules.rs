@@ -0,0 +1,68 @@
+use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
+use nodejs::Runtime;
+
+fn native_module_args() -> NodeArgs {
+    NodeArgs::new()
+        .native_module("math", |env, mut exports| {
+            exports.set_named_property("answer", env.create_int32(42)?)
+        })
+        .native_module("greeter", |env, mut exports| {
+            exports.set_named_property("greeting", env.create_string("hello")?)
+        })
+        .native_module("broken", |_, _| {
+            Err(napi::Error::from_reason("The broken module failed"))
+        })
+}
+
+#[chazi::test(check_reach)]
+fn test_native_modules() {
+    let runtime = Runtime::spawn(native_module_args()).unwrap();
+
+    let answer: i32 = runtime.eval("require('math').answer").unwrap();
+    assert_eq!(answer, 42);
+    let greeting: String = runtime
+        .eval("process._linkedBinding('greeter').greeting")
+        .unwrap();
+    assert_eq!(greeting, "hello");
+    let same: bool = runtime
+        .eval("require('math') === process._linkedBinding('math')")
+        .unwrap();
+    assert!(same);
+
+    let err = runtime
+        .eval::<(), _>("require('broken'); undefined")
+        .err()
+        .unwrap();
+    assert!(err.message().contains("The broken module failed"), "{err}");
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_native_module_reserved_name() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().native_module("__embedder_mod", |_, _| Ok(()))),
+    );
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidArgument);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_native_module_builtin_name() {
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().native_module("node:fs", |_, _| Ok(()))),
+    );
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidArgument);
+
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(NodeArgs::new().native_module("fs", |_, _| Ok(()))),
+    );
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::BootstrapException);
+    chazi::reached::last()
+}
@@ -95,3 +95,31 @@ fn test_require_external_napi() {
     assert!(res.is_ok());
     chazi::reached::last();
 }
+
+#[chazi::test(check_reach)]
+fn test_require_module_root() {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("module_root");
+    let lib = root.join("lib");
+    let shared = root.join("shared");
+    std::fs::create_dir_all(&lib).unwrap();
+    std::fs::create_dir_all(&shared).unwrap();
+    std::fs::write(lib.join("answer.js"), "module.exports = 40;").unwrap();
+    std::fs::write(shared.join("offset.js"), "module.exports = 2;").unwrap();
+
+    let mut result = 0;
+    let res = nodejs::run_napi(
+        |env| {
+            result = env.run_script("require('./lib/answer') + require('offset')")?;
+            Ok(())
+        },
+        Some(
+            nodejs::args::NodeArgs::new()
+                .module_root(&root)
+                .module_paths(["shared"]),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(result, 42);
+    chazi::reached::last()
+}
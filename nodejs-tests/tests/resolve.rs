@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use nodejs::args::NodeArgs;
+use nodejs::error::NodeError;
+use nodejs::resolve::{ResolveKind, ResolvedModule};
+use nodejs::runtime::ModuleNamespace;
+use nodejs::Runtime;
+
+/// Waits for the module on the current thread.
+fn import(runtime: &Runtime, specifier: &str) -> nodejs::Result<ModuleNamespace> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(runtime.import(specifier))
+}
+
+fn resolver_args() -> NodeArgs {
+    let file = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("resolved_file.js");
+    std::fs::write(&file, "module.exports = 'from file';").unwrap();
+
+    NodeArgs::new().module_resolver(move |request| {
+        Ok(match request.specifier() {
+            "app:config" => Some(ResolvedModule::CommonJs(
+                "module.exports = { answer: require('./answer') };".to_string(),
+            )),
+            "./answer"
+                if request
+                    .referrer()
+                    .is_some_and(|r| r.contains("app%3Aconfig")) =>
+            {
+                Some(ResolvedModule::CommonJs("module.exports = 42;".to_string()))
+            }
+            "internal/feature" => Some(ResolvedModule::Module(
+                "export const feature = 'enabled';".to_string(),
+            )),
+            "app:file" => Some(ResolvedModule::File(file.clone())),
+            "app:missing" => return Err(NodeError::generic("No module app:missing")),
+            _ => None,
+        })
+    })
+}
+
+#[chazi::test(check_reach)]
+fn test_resolver_require() {
+    let runtime = Runtime::spawn(resolver_args()).unwrap();
+
+    let answer: i32 = runtime.eval("require('app:config').answer").unwrap();
+    assert_eq!(answer, 42);
+    let from_file: String = runtime.eval("require('app:file')").unwrap();
+    assert_eq!(from_file, "from file");
+    let joined: String = runtime
+        .eval("require('path').posix.join('a', 'b')")
+        .unwrap();
+    assert_eq!(joined, "a/b");
+
+    let err = runtime
+        .eval::<(), _>("require('app:missing'); undefined")
+        .err()
+        .unwrap();
+    assert!(err.message().contains("No module app:missing"), "{err}");
+    let err = runtime
+        .eval::<(), _>("require('internal/feature'); undefined")
+        .err()
+        .unwrap();
+    assert!(err.message().contains("use import instead"), "{err}");
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_resolver_import() {
+    let runtime = Runtime::spawn(resolver_args()).unwrap();
+
+    let module = import(&runtime, "internal/feature").unwrap();
+    let feature: String = module.get("feature").unwrap();
+    assert_eq!(feature, "enabled");
+
+    let config = import(&runtime, "app:config").unwrap();
+    let answer: i32 = config
+        .exec(|_, namespace| {
+            namespace
+                .get_named_property::<napi::JsObject>("default")?
+                .get_named_property("answer")
+        })
+        .unwrap();
+    assert_eq!(answer, 42);
+
+    let err = import(&runtime, "app:missing").err().unwrap();
+    assert!(err.message().contains("No module app:missing"), "{err}");
+
+    drop((module, config));
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_resolver_requests() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let runtime = Runtime::spawn(NodeArgs::new().module_resolver(move |request| {
+        recorded
+            .lock()
+            .unwrap()
+            .push((request.specifier().to_string(), request.kind()));
+        Ok(None)
+    }))
+    .unwrap();
+
+    runtime
+        .eval::<(), _>("require('fs'); require('node:os'); require('./missing.js'); undefined")
+        .err()
+        .unwrap();
+    import(&runtime, "./missing.mjs").err().unwrap();
+
+    assert!(runtime.join().is_ok());
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [
+            ("./missing.js".to_string(), ResolveKind::Require),
+            ("./missing.mjs".to_string(), ResolveKind::Import),
+        ]
+    );
+    chazi::reached::last()
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
+use nodejs::stdio::OutputBuffer;
+use nodejs::{Runtime, Shutdown};
+
+#[chazi::test(check_reach)]
+fn test_runtime_eval() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let answer: i32 = runtime.eval("40+2").unwrap();
+    assert_eq!(answer, 42);
+
+    let status_text: String = runtime.eval("require('http').STATUS_CODES[418]").unwrap();
+    assert_eq!(status_text, "I'm a Teapot");
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_exec_from_threads() {
+    let runtime = Arc::new(Runtime::spawn(NodeArgs::new()).unwrap());
+    runtime
+        .eval::<(), _>("globalThis.counter = 0; undefined")
+        .unwrap();
+
+    let threads = (0..4)
+        .map(|_| {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || {
+                runtime
+                    .exec(|env| {
+                        let res: napi::JsNumber = env.run_script("++globalThis.counter")?;
+                        res.get_int32()
+                    })
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, vec![1, 2, 3, 4]);
+
+    let runtime = Arc::try_unwrap(runtime).ok().unwrap();
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_eval_error() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let res = runtime.eval::<(), _>("throw new Error('oops')");
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Exception);
+    assert!(err.message().contains("oops"));
+
+    let answer: i32 = runtime.eval("40+2").unwrap();
+    assert_eq!(answer, 42);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_join_exit_code() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+    runtime
+        .eval::<(), _>("setImmediate(() => process.exit(42)); undefined")
+        .unwrap();
+
+    let res = runtime.join();
+    assert!(res.is_err());
+    assert_eq!(res.err().unwrap().code(), 42);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_concurrent() {
+    let runtimes = (0..3)
+        .map(|_| Runtime::spawn(NodeArgs::new()).unwrap())
+        .collect::<Vec<_>>();
+
+    for (i, runtime) in runtimes.iter().enumerate() {
+        runtime
+            .eval::<(), _>(format!("globalThis.tenant = {i}; undefined"))
+            .unwrap();
+    }
+
+    for (i, runtime) in runtimes.iter().enumerate() {
+        let tenant: u32 = runtime.eval("globalThis.tenant").unwrap();
+        assert_eq!(tenant, i as u32);
+    }
+
+    runtimes[1]
+        .eval::<(), _>("setInterval(() => {}, 1000); undefined")
+        .unwrap();
+    assert!(runtimes[1].stop().is_ok());
+
+    let answer: i32 = runtimes[2].eval("40+2").unwrap();
+    assert_eq!(answer, 42);
+
+    for runtime in runtimes {
+        assert!(runtime.join().is_ok());
+    }
+
+    chazi::reached::last()
+}
+
+/// Polls the future on the current thread until it completes.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_eval_async() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let answer: i32 =
+        block_on(runtime.eval_async("new Promise((resolve) => setTimeout(() => resolve(42), 10))"))
+            .unwrap();
+    assert_eq!(answer, 42);
+
+    let answer: i32 = block_on(runtime.eval_async("40 + 2")).unwrap();
+    assert_eq!(answer, 42);
+
+    let pending = (0..4)
+        .map(|i| runtime.eval_async::<i32, _>(format!("Promise.resolve({i})")))
+        .collect::<Vec<_>>();
+    let results = std::thread::spawn(move || {
+        pending
+            .into_iter()
+            .map(|f| block_on(f).unwrap())
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(results, vec![0, 1, 2, 3]);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_eval_async_rejected() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let err = block_on(runtime.eval_async::<(), _>("(async () => { throw new Error('oops'); })()"))
+        .err()
+        .unwrap();
+    assert!(err.message().contains("oops"));
+    assert!(err.stack().is_some());
+
+    let err = block_on(runtime.eval_async::<(), _>("throw new Error('sync')"))
+        .err()
+        .unwrap();
+    assert!(err.message().contains("sync"));
+
+    // Thrown while attaching the handlers, and not left pending
+    let err = block_on(runtime.eval_async::<(), _>(
+        "const p = Promise.resolve(); p.then = () => { throw new Error('then'); }; p",
+    ))
+    .err()
+    .unwrap();
+    assert!(err.message().contains("then"), "{err}");
+    let answer: i32 = runtime.eval("42").unwrap();
+    assert_eq!(answer, 42);
+
+    assert!(runtime.join().is_ok());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_eval_async_stopped() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let never = runtime.eval_async::<(), _>("new Promise(() => {})");
+    runtime.stop().unwrap();
+    assert!(block_on(never).is_err());
+
+    let _ = runtime.join();
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_drop_stops_instance() {
+    let stdout = OutputBuffer::new();
+    let runtime = Runtime::spawn(NodeArgs::new().stdout(stdout.clone())).unwrap();
+    runtime
+        .eval::<(), _>("setInterval(() => process.stdout.write('tick'), 10); undefined")
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    drop(runtime);
+
+    // Output that was written before the instance stopped
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!stdout.take().is_empty());
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(stdout.take().is_empty());
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_terminate() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    let looping = runtime.eval_async::<(), _>("for (;;) {}");
+    runtime.terminate().unwrap();
+    assert!(block_on(looping).is_err());
+
+    assert_eq!(runtime.join().err().unwrap().kind(), ErrorKind::Terminated);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_shutdown_graceful() {
+    let stdout = OutputBuffer::new();
+    let runtime = Runtime::spawn(NodeArgs::new().stdout(stdout.clone())).unwrap();
+
+    runtime
+        .eval::<(), _>(
+            "const timer = setInterval(() => {}, 1000);
+            process.on('shutdown', () => clearInterval(timer));
+            process.on('beforeExit', () => process.stdout.write('beforeExit '));
+            process.on('exit', () => process.stdout.write('exit'));
+            undefined",
+        )
+        .unwrap();
+
+    let shutdown = runtime.shutdown(Duration::from_secs(10)).unwrap();
+    assert_eq!(shutdown, Shutdown::Graceful);
+    assert_eq!(stdout.to_string_lossy(), "beforeExit exit");
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_runtime_shutdown_forced() {
+    let runtime = Runtime::spawn(NodeArgs::new()).unwrap();
+
+    runtime
+        .eval::<(), _>("setInterval(() => {}, 1000); undefined")
+        .unwrap();
+
+    let shutdown = runtime.shutdown(Duration::from_millis(500)).unwrap();
+    assert_eq!(shutdown, Shutdown::Forced);
+    chazi::reached::last()
+}
@@ -3,6 +3,7 @@ use std::sync::Mutex;
 use std::time::Duration;
 
 use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
 use nodejs::neon::result::NeonResult;
 use nodejs::neon::types::{JsArray, JsString};
 use nodejs::neon::{context::Context, reflect::eval, types::JsNumber};
@@ -86,6 +87,27 @@ fn test_simple_napi_safe() {
     chazi::reached::last()
 }
 
+#[chazi::test(check_reach)]
+fn test_run_multiple_times() {
+    for i in 0..3 {
+        let mut answer = 0;
+        let res = nodejs::run_napi(
+            |env| {
+                let res: napi::JsNumber = env.run_script(format!("40+{i}"))?;
+                answer = res.get_int32()?;
+
+                Ok(())
+            },
+            None,
+        );
+
+        assert!(res.is_ok(), "{}", res.err().unwrap());
+        assert_eq!(answer, 40 + i);
+    }
+
+    chazi::reached::last()
+}
+
 #[chazi::test(check_reach)]
 fn test_process_exit_nonzero() {
     let res = unsafe {
@@ -99,8 +121,9 @@ fn test_process_exit_nonzero() {
         )
     };
 
-    assert!(res.is_err());
-    assert_eq!(res.err().unwrap().code(), 42);
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NonZeroExit);
+    assert_eq!(err.code(), 42);
     chazi::reached::last()
 }
 
@@ -186,6 +209,74 @@ fn test_uncaught_error_napi() {
     chazi::reached::last()
 }
 
+#[chazi::test(check_reach)]
+fn test_uncaught_exception_kind() {
+    let res = nodejs::run_napi(
+        |env| {
+            env.run_script::<_, napi::JsUnknown>(
+                "setImmediate(() => { throw new Error('oops') })",
+            )?;
+            Ok(())
+        },
+        None,
+    );
+
+    let err = res.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::UncaughtException);
+    assert_eq!(err.code(), 1);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_uncaught_exception_details() {
+    let res = nodejs::run_napi(
+        |env| {
+            env.run_script::<_, napi::JsUnknown>(
+                "setImmediate(function failLater() { \
+                    throw new RangeError('oops', { cause: new Error('inner') }) \
+                })",
+            )?;
+            Ok(())
+        },
+        None,
+    );
+
+    let err = res.err().unwrap();
+    assert_eq!(err.message(), "Uncaught RangeError: oops");
+    let exception = err.exception().unwrap();
+    assert_eq!(exception.name(), Some("RangeError"));
+    assert_eq!(exception.message(), Some("oops"));
+    assert!(exception.stack().unwrap().contains("failLater"));
+    assert_eq!(err.stack(), exception.stack());
+    assert!(exception.cause().unwrap().contains("inner"));
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_uncaught_exception_not_error() {
+    let res = nodejs::run_napi(
+        |env| {
+            env.run_script::<_, napi::JsUnknown>("setImmediate(() => { throw 'oops' })")?;
+            Ok(())
+        },
+        None,
+    );
+
+    let err = res.err().unwrap();
+    let exception = err.exception().unwrap();
+    assert_eq!(exception.name(), None);
+    assert_eq!(exception.message(), Some("'oops'"));
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_bootstrap_exception_kind() {
+    let res = nodejs::run_napi(|_| Err(napi::Error::from_reason("oops")), None);
+
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::BootstrapException);
+    chazi::reached::last()
+}
+
 #[chazi::test(check_reach)]
 fn test_manual_stop() {
     let result = Arc::new(Mutex::new(None));
@@ -217,3 +308,62 @@ fn test_manual_stop() {
 
     chazi::reached::last()
 }
+
+#[chazi::test(check_reach)]
+fn test_stop_with_handle() {
+    let handle = Arc::new(nodejs::InstanceHandle::new());
+    let thread_handle = handle.clone();
+    let thread = std::thread::spawn(move || {
+        nodejs::run_napi_with_handle(
+            |env| {
+                env.run_script::<_, napi::JsUnknown>("setInterval(() => {}, 1000)")?;
+                Ok(())
+            },
+            None,
+            &thread_handle,
+        )
+    });
+
+    std::thread::sleep(Duration::from_secs(1));
+    let res = nodejs::run_napi_with_handle(|_| Ok(()), None, &handle);
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::AlreadyRunning);
+
+    let code = handle.stop();
+    assert!(code.is_ok(), "{}", code.err().unwrap());
+
+    let res = thread.join().unwrap();
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+
+    let code = handle.stop();
+    assert_eq!(code.err().unwrap().kind(), ErrorKind::StopFailed);
+
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_terminate_with_handle() {
+    let handle = Arc::new(nodejs::InstanceHandle::new());
+    let thread_handle = handle.clone();
+    let thread = std::thread::spawn(move || {
+        nodejs::run_napi_with_handle(
+            |env| {
+                env.run_script::<_, napi::JsUnknown>("for (;;) {}")?;
+                Ok(())
+            },
+            None,
+            &thread_handle,
+        )
+    });
+
+    std::thread::sleep(Duration::from_secs(1));
+    let code = handle.terminate();
+    assert!(code.is_ok(), "{}", code.err().unwrap());
+
+    let res = thread.join().unwrap();
+    assert_eq!(res.err().unwrap().kind(), ErrorKind::Terminated);
+
+    let code = handle.terminate();
+    assert_eq!(code.err().unwrap().kind(), ErrorKind::StopFailed);
+
+    chazi::reached::last()
+}
@@ -0,0 +1,72 @@
+use nodejs::args::NodeArgs;
+use nodejs::error::ErrorKind;
+use nodejs::snapshot::Snapshot;
+
+fn create_snapshot() -> Snapshot {
+    Snapshot::create(
+        "globalThis.answer = 40 + 2; \
+         globalThis.joined = require('path').posix.join('a', 'b');",
+        None,
+    )
+    .unwrap()
+}
+
+#[chazi::test(check_reach)]
+fn test_snapshot_run() {
+    let snapshot = create_snapshot();
+
+    let mut values = (0, String::new());
+    let res = nodejs::run_napi(
+        |env| {
+            values = (
+                env.run_script("globalThis.answer")?,
+                env.run_script("globalThis.joined")?,
+            );
+            Ok(())
+        },
+        Some(NodeArgs::new().snapshot(snapshot)),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(values, (42, "a/b".to_string()));
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_snapshot_file() {
+    let path = std::env::temp_dir().join(format!("nodejs-snapshot-{}.blob", std::process::id()));
+    create_snapshot().write(&path).unwrap();
+    let snapshot = Snapshot::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut answer = 0;
+    let res = nodejs::run_napi(
+        |env| {
+            answer = env.run_script("globalThis.answer")?;
+            Ok(())
+        },
+        Some(NodeArgs::new().snapshot(snapshot)),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(answer, 42);
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_snapshot_invalid() {
+    let err = Snapshot::from_bytes(b"not a snapshot".to_vec())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidSnapshot);
+
+    // The third line of the header is the Node.js version
+    let bytes = create_snapshot().as_bytes().to_vec();
+    let lines: Vec<_> = bytes.splitn(4, |&b| b == b'\n').collect();
+    let tampered = [lines[0], lines[1], b"v0.0.0", lines[3]].join(&b'\n');
+    let err = Snapshot::from_bytes(tampered).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidSnapshot);
+    assert!(err.message().contains("v0.0.0"), "{}", err.message());
+
+    chazi::reached::last()
+}
@@ -0,0 +1,148 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+use nodejs::args::{MainScript, NodeArgs};
+use nodejs::stdio::{InputSource, OutputBuffer, OutputSink};
+
+#[chazi::test(check_reach)]
+fn test_capture_output() {
+    let stdout = OutputBuffer::new();
+    let stderr = OutputBuffer::new();
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(MainScript::Source {
+                    source: "console.log('one'); \
+                        setTimeout(() => process.stdout.write('three\\n'), 10); \
+                        process.stdout.write('two\\n'); \
+                        console.error('error');"
+                        .to_string(),
+                    filename: "main.js".to_string(),
+                })
+                .stdout(stdout.clone())
+                .stderr(stderr.clone()),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(stdout.to_string_lossy(), "one\ntwo\nthree\n");
+    assert_eq!(stderr.to_string_lossy(), "error\n");
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_output_callback() {
+    let chunks = Arc::new(Mutex::new(Vec::<String>::new()));
+    let sink_chunks = chunks.clone();
+    let res = nodejs::run_napi(
+        |env| {
+            env.run_script::<_, napi::JsUnknown>(
+                "for (let i = 0; i < 3; i++) process.stdout.write(String(i))",
+            )?;
+            Ok(())
+        },
+        Some(NodeArgs::new().stdout(OutputSink::from_fn(move |chunk| {
+            let chunk = String::from_utf8_lossy(chunk).into_owned();
+            sink_chunks.lock().unwrap().push(chunk);
+        }))),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(*chunks.lock().unwrap(), vec!["0", "1", "2"]);
+    chazi::reached::last()
+}
+
+const UPPERCASE_LINES: &str = "\
+const readline = require('readline');
+(async () => {
+  for await (const line of readline.createInterface({ input: process.stdin })) {
+    console.log(line.toUpperCase());
+  }
+})();
+";
+
+#[chazi::test(check_reach)]
+fn test_stdin_reader() {
+    let stdout = OutputBuffer::new();
+    let res = nodejs::run_napi(
+        |_| Ok(()),
+        Some(
+            NodeArgs::new()
+                .main_script(MainScript::Source {
+                    source: UPPERCASE_LINES.to_string(),
+                    filename: "main.js".to_string(),
+                })
+                .stdin(InputSource::new(std::io::Cursor::new("one\ntwo\n")))
+                .stdout(stdout.clone()),
+        ),
+    );
+
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(stdout.to_string_lossy(), "ONE\nTWO\n");
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_stdin_channel() {
+    let (tx, rx) = mpsc::channel();
+    let stdout = OutputBuffer::new();
+    let args = NodeArgs::new()
+        .main_script(MainScript::Source {
+            source: UPPERCASE_LINES.to_string(),
+            filename: "main.js".to_string(),
+        })
+        .stdin(InputSource::from_channel(rx))
+        .stdout(stdout.clone());
+
+    let thread = std::thread::spawn(move || nodejs::run_napi(|_| Ok(()), Some(args)));
+    tx.send(b"hello ".to_vec()).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    tx.send(b"world\n".to_vec()).unwrap();
+    drop(tx);
+
+    let res = thread.join().unwrap();
+    assert!(res.is_ok(), "{}", res.err().unwrap());
+    assert_eq!(stdout.to_string_lossy(), "HELLO WORLD\n");
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_stdin_shared_between_runs() {
+    let (tx, rx) = mpsc::channel();
+    let stdin = InputSource::from_channel(rx);
+    let run = |source: &str| {
+        let stdout = OutputBuffer::new();
+        let res = nodejs::run_napi(
+            |_| Ok(()),
+            Some(
+                NodeArgs::new()
+                    .main_script(MainScript::Source {
+                        source: source.to_string(),
+                        filename: "main.js".to_string(),
+                    })
+                    .stdin(stdin.clone())
+                    .stdout(stdout.clone()),
+            ),
+        );
+        assert!(res.is_ok(), "{}", res.err().unwrap());
+        stdout.to_string_lossy()
+    };
+
+    // The stream requests the next chunk before the run exits
+    tx.send(b"one\n".to_vec()).unwrap();
+    let first = run("process.stdin.once('data', (chunk) => {
+        process.stdout.write(chunk);
+        setImmediate(() => process.exit());
+    });");
+    assert_eq!(first, "one\n");
+
+    // Read by the first run after it has ended
+    tx.send(b"two\n".to_vec()).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    tx.send(b"three\n".to_vec()).unwrap();
+    drop(tx);
+
+    let second = run("process.stdin.pipe(process.stdout);");
+    assert_eq!(second, "two\nthree\n");
+    chazi::reached::last()
+}
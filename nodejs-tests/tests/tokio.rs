@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nodejs::args::NodeArgs;
+use nodejs::error::NodeError;
+use nodejs::Runtime;
+
+#[chazi::test(check_reach)]
+fn test_tokio_async_fn() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let runtime = Runtime::spawn_async(NodeArgs::new()).await.unwrap();
+        runtime
+            .define_async_fn("double", |value: i32| async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok(value * 2)
+            })
+            .unwrap();
+        runtime
+            .define_async_fn("fail", |message: String| async move {
+                Err::<(), _>(NodeError::generic(message))
+            })
+            .unwrap();
+
+        let answer: i32 = runtime.eval_async("double(21)").await.unwrap();
+        assert_eq!(answer, 42);
+
+        let message: String = runtime
+            .eval_async("fail('nope').catch((e) => e.message)")
+            .await
+            .unwrap();
+        assert_eq!(message, "nope");
+
+        // Pending tasks keep the event loop alive
+        runtime
+            .eval::<(), _>(
+                "double(1).then((value) => { process.exitCode = value === 2 ? 0 : 3; }); undefined",
+            )
+            .unwrap();
+        assert!(runtime.join_async().await.is_ok());
+    });
+
+    chazi::reached::last()
+}
+
+#[chazi::test(check_reach)]
+fn test_tokio_stop_aborts_tasks() {
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let aborted = Arc::new(AtomicBool::new(false));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        let runtime = Runtime::spawn_async(NodeArgs::new()).await.unwrap();
+        let task_aborted = aborted.clone();
+        runtime
+            .define_async_fn("forever", move |_: ()| {
+                let guard = SetOnDrop(task_aborted.clone());
+                async move {
+                    let _guard = guard;
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                    Ok(())
+                }
+            })
+            .unwrap();
+
+        runtime.eval::<(), _>("forever(); undefined").unwrap();
+        runtime.stop().unwrap();
+        let _ = runtime.join_async().await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    });
+
+    assert!(aborted.load(Ordering::SeqCst));
+    chazi::reached::last()
+}
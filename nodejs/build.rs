@@ -7,6 +7,9 @@ use std::io;
 use std::path::{Path, PathBuf};
 use strum::Display;
 
+/// The libnode release to download. Releases built from `libnode/` are named after the Node.js
+/// version and `NODE_EMBEDDING_API_VERSION`, e.g. `v21.7.3-api1`; switch to that name once it is
+/// published. Until then, build libnode locally and point `LIBNODE_PATH` at it.
 const NODE_VERSION: &str = "v21.7.3";
 const USER: &str = "MarkusJx";
 const REPO: &str = "rust-nodejs";
@@ -238,7 +241,15 @@ fn main() -> anyhow::Result<()> {
         libnode_extracted
     };
 
-    std::fs::copy(libnode_path.join("sys.rs"), out_dir.join("sys.rs"))?;
+    let bindings = std::fs::read_to_string(libnode_path.join("sys.rs"))?;
+    if !bindings.contains("NODE_EMBEDDING_API_VERSION") {
+        anyhow::bail!(
+            "libnode in {:?} was not built with the embedding API from libnode/patch, \
+            set LIBNODE_PATH to a local build",
+            libnode_path
+        );
+    }
+    std::fs::write(out_dir.join("sys.rs"), bindings)?;
     let lib_path = libnode_path.join("lib");
 
     println!(
@@ -1,16 +1,98 @@
-use crate::error::NodeError;
+use std::path::PathBuf;
+
+#[cfg(feature = "napi")]
+use napi::{Env, JsObject};
+
+#[cfg(feature = "bundle")]
+use crate::bundle::Bundle;
+use crate::error::{ErrorKind, NodeError};
+use crate::global::GlobalValue;
+use crate::heap::HeapLimitHandler;
+use crate::limits::TimeLimits;
+#[cfg(feature = "napi")]
+use crate::native::NativeModule;
+use crate::options::NodeOptions;
+use crate::resolve::{ModuleResolver, ResolveRequest, ResolvedModule};
+use crate::snapshot::Snapshot;
+use crate::stdio::{InputSource, OutputSink};
+
+/// The script Node.js runs after it has been started.
+///
+/// Like `node app.js`, the resolved filename becomes `process.argv[1]`,
+/// so the script arguments start at `process.argv[2]`.
+#[derive(Debug, Clone)]
+pub enum MainScript {
+    /// Runs the file at the given path, like `node app.js`.
+    /// The path is resolved against [`NodeArgs::module_root`].
+    /// `.mjs` files and files in a `"type": "module"` package are run as ES modules.
+    File(PathBuf),
+    /// Runs the given source as a CommonJS module.
+    /// Relative `require` calls are resolved against the virtual `filename`,
+    /// which is resolved against [`NodeArgs::module_root`].
+    Source { source: String, filename: String },
+    /// Runs the given source as an ES module.
+    /// Relative imports are resolved against the virtual `filename`,
+    /// which is resolved against [`NodeArgs::module_root`].
+    Module { source: String, filename: String },
+}
 
 #[derive(Debug, Clone)]
 pub struct NodeArgs {
     pub(crate) args: Vec<String>,
+    pub(crate) exec_args: Vec<String>,
+    pub(crate) options: NodeOptions,
+    pub(crate) script_args: Vec<String>,
     pub(crate) insert_default_process_arg: bool,
+    pub(crate) main_script: Option<MainScript>,
+    pub(crate) builtin_bootstrap: bool,
+    pub(crate) globals: Vec<(String, GlobalValue)>,
+    #[cfg(feature = "napi")]
+    pub(crate) native_modules: Vec<NativeModule>,
+    pub(crate) env_clear: bool,
+    pub(crate) env_vars: Vec<(String, Option<String>)>,
+    pub(crate) module_root: Option<PathBuf>,
+    #[cfg(feature = "bundle")]
+    pub(crate) bundle: Option<Bundle>,
+    pub(crate) module_paths: Vec<PathBuf>,
+    pub(crate) module_resolver: Option<ModuleResolver>,
+    pub(crate) stdout: Option<OutputSink>,
+    pub(crate) stderr: Option<OutputSink>,
+    pub(crate) stdin: Option<InputSource>,
+    pub(crate) console_runtime: Option<String>,
+    pub(crate) heap_limit: usize,
+    pub(crate) heap_limit_handler: Option<HeapLimitHandler>,
+    pub(crate) time_limits: TimeLimits,
+    pub(crate) snapshot: Option<Snapshot>,
 }
 
 impl NodeArgs {
     pub fn new() -> Self {
         Self {
             args: Vec::new(),
+            exec_args: Vec::new(),
+            options: NodeOptions::new(),
+            script_args: Vec::new(),
             insert_default_process_arg: true,
+            main_script: None,
+            builtin_bootstrap: false,
+            globals: Vec::new(),
+            #[cfg(feature = "napi")]
+            native_modules: Vec::new(),
+            env_clear: false,
+            env_vars: Vec::new(),
+            module_root: None,
+            #[cfg(feature = "bundle")]
+            bundle: None,
+            module_paths: Vec::new(),
+            module_resolver: None,
+            stdout: None,
+            stderr: None,
+            stdin: None,
+            console_runtime: None,
+            heap_limit: 0,
+            heap_limit_handler: None,
+            time_limits: TimeLimits::new(),
+            snapshot: None,
         }
     }
 
@@ -23,15 +105,269 @@ impl NodeArgs {
         self
     }
 
+    /// Sets the Node.js and V8 options, such as `--max-old-space-size=4096`.
+    /// They are visible in `process.execArgv`, but not in `process.argv`.
+    ///
+    /// Arguments that are not options, options unknown to Node.js and V8, and options
+    /// that make Node.js exit early such as `--version`, are rejected before Node.js is started.
+    ///
+    /// V8 options and Node.js options of the whole process, such as `--title`, are
+    /// applied by the first run in a process and kept by later runs. A later run
+    /// fails with [`ErrorKind::InvalidArgument`] if its V8 options differ from the
+    /// first run, other options of the whole process are ignored. Other Node.js
+    /// options, such as `--no-deprecation`, apply to each run separately.
+    pub fn exec_args<T, I>(mut self, exec_args: T) -> Self
+    where
+        T: IntoIterator<Item = I>,
+        I: ToString,
+    {
+        self.exec_args = exec_args.into_iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    /// Sets typed Node.js and V8 options. They are passed before the raw [`NodeArgs::exec_args`].
+    /// Invalid or conflicting options are rejected before Node.js is started.
+    pub fn options(mut self, options: NodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the arguments appended to `process.argv`.
+    /// Unlike [`NodeArgs::args`], they are never parsed as Node.js options.
+    pub fn script_args<T, I>(mut self, script_args: T) -> Self
+    where
+        T: IntoIterator<Item = I>,
+        I: ToString,
+    {
+        self.script_args = script_args.into_iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
     pub fn insert_default_process_arg(mut self, insert_default_process_arg: bool) -> Self {
         self.insert_default_process_arg = insert_default_process_arg;
         self
     }
 
+    /// Sets the script to run instead of the built-in bootstrap.
+    /// The N-API module init function is still called before the script runs.
+    pub fn main_script(mut self, main_script: MainScript) -> Self {
+        self.main_script = Some(main_script);
+        self
+    }
+
+    /// Whether to run the built-in bootstrap before the main script.
+    /// The built-in bootstrap defines `globalThis.require` and `globalThis.embedVars`.
+    /// It is always used if no main script is set.
+    pub fn builtin_bootstrap(mut self, builtin_bootstrap: bool) -> Self {
+        self.builtin_bootstrap = builtin_bootstrap;
+        self
+    }
+
+    /// Defines a property on `globalThis` before the N-API module init function
+    /// and the main script run. Defining the same name twice keeps the last value.
+    pub fn global<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<GlobalValue>,
+    {
+        let name = name.into();
+        self.globals.retain(|(existing, _)| existing != &name);
+        self.globals.push((name, value.into()));
+        self
+    }
+
+    /// Registers an N-API module implemented in Rust under `name`, in addition to the
+    /// module of the init function. JavaScript loads it with `require(name)` or
+    /// `process._linkedBinding(name)`, which calls `init` with the exports of the module
+    /// the first time. Registering the same name twice keeps the last module.
+    ///
+    /// The name must not be the name of a Node.js builtin module such as `fs`,
+    /// otherwise the run fails with [`ErrorKind::BootstrapException`].
+    #[cfg(feature = "napi")]
+    pub fn native_module<N, F>(mut self, name: N, init: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(Env, JsObject) -> napi::Result<()> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.native_modules.retain(|module| module.name() != name);
+        self.native_modules.push(NativeModule::new(name, init));
+        self
+    }
+
+    /// Sets the directory `globalThis.require` and the main script are resolved against.
+    /// Defaults to the current working directory at startup.
+    /// A relative path is resolved against the current working directory.
+    pub fn module_root<P: Into<PathBuf>>(mut self, module_root: P) -> Self {
+        self.module_root = Some(module_root.into());
+        self
+    }
+
+    /// Loads the main script and the modules from the bundle. It is extracted when
+    /// the run starts, see [`Bundle::extract`], and used as the module root.
+    /// A relative [`NodeArgs::module_root`] is resolved against the bundle.
+    #[cfg(feature = "bundle")]
+    pub fn bundle(mut self, bundle: Bundle) -> Self {
+        self.bundle = Some(bundle);
+        self
+    }
+
+    /// Sets directories searched by `require` after the `node_modules` directories,
+    /// like `NODE_PATH`. Relative paths are resolved against [`NodeArgs::module_root`].
+    /// ES modules do not use these paths.
+    pub fn module_paths<T, P>(mut self, module_paths: T) -> Self
+    where
+        T: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.module_paths = module_paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Calls `f` on the event loop thread before Node.js resolves a `require` or an `import`,
+    /// except for built-in modules. Returning `None` lets Node.js resolve the specifier,
+    /// returning an error throws it in JavaScript.
+    ///
+    /// Imports are resolved by module customization hooks, which wait for the event loop
+    /// thread, so `import.meta.resolve` must not be called while a resolver is set.
+    pub fn module_resolver<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ResolveRequest<'_>) -> crate::Result<Option<ResolvedModule>> + Send + Sync + 'static,
+    {
+        self.module_resolver = Some(ModuleResolver::new(f));
+        self
+    }
+
+    /// Redirects `process.stdout`, and with it `console.log`, to the sink
+    /// instead of the stdout of the host process.
+    pub fn stdout<S: Into<OutputSink>>(mut self, sink: S) -> Self {
+        self.stdout = Some(sink.into());
+        self
+    }
+
+    /// Redirects `process.stderr`, and with it `console.error`, to the sink
+    /// instead of the stderr of the host process.
+    /// Errors reported by Node.js itself, such as uncaught exceptions, are still
+    /// printed to the stderr of the host process.
+    pub fn stderr<S: Into<OutputSink>>(mut self, sink: S) -> Self {
+        self.stderr = Some(sink.into());
+        self
+    }
+
+    /// Replaces `process.stdin` with a stream that reads from the source
+    /// instead of the stdin of the host process.
+    pub fn stdin(mut self, source: InputSource) -> Self {
+        self.stdin = Some(source);
+        self
+    }
+
+    /// Turns calls of `console.debug`, `console.log`, `console.info`, `console.warn`,
+    /// `console.error` and `console.trace` into `tracing` events instead of printing them.
+    ///
+    /// The events have the target `nodejs::console` and the level of the method,
+    /// `console.log` and `console.info` are `INFO`. Their message is formatted like
+    /// the console prints it, and their fields are:
+    /// - `runtime`: the given name, to tell several runtimes apart
+    /// - `method`: the name of the console method
+    /// - `args`: each argument, inspected like the console prints it
+    ///
+    /// `tracing` requires the target of an event to be known at compile time, so all
+    /// runtimes share the target. Filter the events of a runtime by its `runtime` field,
+    /// for example with the `tracing-subscriber` directive
+    /// `nodejs::console[{runtime=worker}]=debug`.
+    #[cfg(feature = "tracing")]
+    pub fn trace_console<N: Into<String>>(mut self, runtime: N) -> Self {
+        self.console_runtime = Some(runtime.into());
+        self
+    }
+
+    /// Limits the JavaScript heap of the run to `bytes`, checked after each garbage collection.
+    ///
+    /// Once the heap reaches the limit, or the limit of V8 itself, only this run is stopped
+    /// and it returns an error of the kind [`ErrorKind::OutOfMemory`] instead of aborting
+    /// the process. See [`NodeArgs::on_heap_limit`] to raise the limit instead.
+    pub fn heap_limit(mut self, bytes: usize) -> Self {
+        self.heap_limit = bytes;
+        self
+    }
+
+    /// Calls `f` on the event loop thread once the heap has reached its limit, with the
+    /// limit and the number of bytes in use. Returning a greater limit lets the run
+    /// continue, returning `None` stops it like [`NodeArgs::heap_limit`] describes.
+    pub fn on_heap_limit<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, usize) -> Option<usize> + Send + Sync + 'static,
+    {
+        self.heap_limit_handler = Some(HeapLimitHandler::new(f));
+        self
+    }
+
+    /// Limits the time the whole run may take, measured from its start.
+    /// Once a limit is exceeded, the run is stopped and returns an error of the kind
+    /// [`ErrorKind::TimeLimitExceeded`] or [`ErrorKind::CpuTimeLimitExceeded`].
+    pub fn time_limits(mut self, limits: TimeLimits) -> Self {
+        self.time_limits = limits;
+        self
+    }
+
+    /// Starts the run from the snapshot instead of bootstrapping Node.js.
+    /// The main script and the module init function still run, and see the
+    /// state of `globalThis` the snapshot was created with.
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Sets an environment variable visible in `process.env`.
+    /// The environment of the host process is not modified.
+    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let key = key.into();
+        self.env_vars.retain(|(existing, _)| existing != &key);
+        self.env_vars.push((key, Some(value.into())));
+        self
+    }
+
+    /// Sets several environment variables, see [`NodeArgs::env`].
+    pub fn envs<T, K, V>(mut self, vars: T) -> Self
+    where
+        T: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (key, value) in vars {
+            self = self.env(key, value);
+        }
+        self
+    }
+
+    /// Hides an inherited environment variable from `process.env`.
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.env_vars.retain(|(existing, _)| existing != &key);
+        self.env_vars.push((key, None));
+        self
+    }
+
+    /// Starts `process.env` from an empty environment instead of inheriting
+    /// the environment of the host process. Only variables set using
+    /// [`NodeArgs::env`] are visible.
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self.env_vars.retain(|(_, value)| value.is_some());
+        self
+    }
+
     pub(crate) fn get_args(&self) -> crate::Result<Vec<String>> {
-        let first_arg = std::env::args()
-            .next()
-            .ok_or_else(|| NodeError::generic("Failed to get the first run argument"))?;
+        let first_arg = std::env::args().next().ok_or_else(|| {
+            NodeError::from_kind(
+                ErrorKind::InvalidArgument,
+                "Failed to get the first run argument",
+            )
+        })?;
 
         let mut args = self.args.clone();
         if (args.first().is_none() || args.first().unwrap() != &first_arg)
@@ -42,6 +378,63 @@ impl NodeArgs {
 
         Ok(args)
     }
+
+    /// Returns the environment as `NAME=value` strings, or `None` if
+    /// the environment of the host process is used as-is.
+    /// Inherited variables that are not valid Unicode are skipped.
+    pub(crate) fn get_env_vars(&self) -> crate::Result<Option<Vec<String>>> {
+        if !self.env_clear && self.env_vars.is_empty() {
+            return Ok(None);
+        }
+
+        let mut vars: Vec<(String, String)> = if self.env_clear {
+            Vec::new()
+        } else {
+            std::env::vars_os()
+                .filter_map(|(key, value)| {
+                    Some((key.into_string().ok()?, value.into_string().ok()?))
+                })
+                .collect()
+        };
+
+        for (key, value) in &self.env_vars {
+            if key.is_empty() || key.contains('=') {
+                return Err(NodeError::from_kind(
+                    ErrorKind::InvalidArgument,
+                    format!("Invalid environment variable name: {key:?}"),
+                ));
+            }
+
+            vars.retain(|(existing, _)| existing != key);
+            if let Some(value) = value {
+                vars.push((key.clone(), value.clone()));
+            }
+        }
+
+        Ok(Some(
+            vars.into_iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect(),
+        ))
+    }
+
+    /// Extracts the bundle, if any, and resolves the module root against it.
+    #[cfg(feature = "bundle")]
+    pub(crate) fn extract_bundle(mut self) -> crate::Result<Self> {
+        if let Some(bundle) = &self.bundle {
+            let root = bundle.extract()?;
+            self.module_root = Some(match &self.module_root {
+                Some(module_root) => root.join(module_root),
+                None => root,
+            });
+        }
+        Ok(self)
+    }
+
+    pub(crate) fn get_exec_args(&self) -> crate::Result<Vec<String>> {
+        crate::options::merge_exec_args(&self.options, &self.exec_args)
+            .map_err(|e| NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e))
+    }
 }
 
 impl Default for NodeArgs {
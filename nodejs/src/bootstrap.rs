@@ -0,0 +1,528 @@
+use std::fmt::Write;
+
+use crate::args::{MainScript, NodeArgs};
+
+/// Reports exceptions that are not handled by JavaScript to the embedding layer,
+/// so the run fails with an uncaught exception error that carries its details.
+const REPORT_UNCAUGHT_EXCEPTIONS: &str = "\
+{
+  const internal = process._linkedBinding('__embedder_internal');
+  const { inspect, types } = require('util');
+  const get = (fn) => {
+    try {
+      return fn();
+    } catch {
+      return undefined;
+    }
+  };
+  process.on('uncaughtExceptionMonitor', (err) => {
+    if (process.listenerCount('uncaughtException') > 0 ||
+        process.hasUncaughtExceptionCaptureCallback()) {
+      return;
+    }
+    if (!types.isNativeError(err)) {
+      internal.reportUncaughtException(undefined, get(() => inspect(err)));
+      return;
+    }
+    internal.reportUncaughtException(
+      get(() => String(err.name)),
+      get(() => String(err.message)),
+      get(() => err.stack),
+      get(() => ('cause' in err ? inspect(err.cause) : undefined)),
+    );
+  });
+}
+";
+
+/// Replaces `process.stdout` and `process.stderr` with streams that write to the
+/// Rust sinks. Writes are synchronous, so their order is kept.
+const REDIRECT_OUTPUT: &str = "\
+const redirectOutput = (name, fd) => {
+  const internal = process._linkedBinding('__embedder_internal');
+  const stream = new (require('stream').Writable)({
+    write(chunk, encoding, callback) {
+      const written = internal.writeOutput(fd, chunk);
+      callback(written ? null : new Error(`Failed to write to process.${name}`));
+    },
+  });
+  stream.fd = fd;
+  Object.defineProperty(process, name, {
+    configurable: true,
+    enumerable: true,
+    get: () => stream,
+  });
+};
+";
+
+/// Replaces `process.stdin` with a stream that reads from the Rust source.
+/// The stream is created on first use, like the built-in one.
+const REDIRECT_INPUT: &str = "\
+{
+  const internal = process._linkedBinding('__embedder_internal');
+  let stdin;
+  Object.defineProperty(process, 'stdin', {
+    configurable: true,
+    enumerable: true,
+    get: () => {
+      if (!stdin) {
+        stdin = new (require('stream').Readable)({
+          read() {
+            internal.readInput((err, chunk) => (err ? this.destroy(err) : this.push(chunk)));
+          },
+        });
+        stdin.fd = 0;
+      }
+      return stdin;
+    },
+  });
+}
+";
+
+/// Passes console calls to the embedding layer instead of printing them.
+/// `console.trace` adds the stack trace to the message, like the built-in one.
+const REDIRECT_CONSOLE: &str = "\
+{
+  const internal = process._linkedBinding('__embedder_internal');
+  const { format, inspect } = require('util');
+  for (const method of ['debug', 'log', 'info', 'warn', 'error', 'trace']) {
+    const log = (...args) => {
+      let message = format(...args);
+      if (method === 'trace') {
+        const trace = { name: 'Trace', message };
+        Error.captureStackTrace(trace, log);
+        message = trace.stack;
+      }
+      const inspected = args.map((arg) => (typeof arg === 'string' ? arg : inspect(arg)));
+      internal.logConsole(method, message, inspected);
+    };
+    Object.defineProperty(console, method, {
+      value: log,
+      writable: true,
+      enumerable: true,
+      configurable: true,
+    });
+  }
+}
+";
+
+/// Same as the built-in bootstrap of libnode, without loading the embedder module.
+const BUILTIN_BOOTSTRAP: &str = "\
+const publicRequire = require('module').createRequire(moduleRoot + '/');
+globalThis.require = publicRequire;
+globalThis.embedVars = { nön_ascıı: '🏳️‍🌈' };
+";
+
+/// Appends the extra search paths to the lookup paths of every CommonJS module.
+const MODULE_PATHS: &str = "\
+{
+  const Module = require('module');
+  const nodeModulePaths = Module._nodeModulePaths;
+  Module._nodeModulePaths = (from) => [...nodeModulePaths(from), ...modulePaths];
+}
+";
+
+/// Resolves `require` and `import` with the Rust resolver before Node.js does.
+/// Modules created from source are served by the CommonJS loader or by the module
+/// customization hooks, which ask the event loop thread to resolve imports.
+const MODULE_RESOLVER: &str = "\
+const internal = process._linkedBinding('__embedder_internal');
+const Module = require('module');
+const path = require('path');
+const url = require('url');
+const sources = new Map();
+const formats = [undefined, 'file', 'commonjs', 'module'];
+const isRelative = (specifier) =>
+  specifier === '.' || specifier === '..' || specifier.startsWith('./') ||
+  specifier.startsWith('../') || path.isAbsolute(specifier);
+const resolve = (specifier, referrer, isImport) => {
+  const resolved = internal.resolveModule(specifier, referrer, isImport);
+  if (resolved === undefined) {
+    return undefined;
+  }
+  const format = formats[resolved[0]];
+  if (format === 'file') {
+    return { format, filename: path.resolve(moduleRoot, resolved[1]) };
+  }
+  const base = referrer && path.isAbsolute(referrer) ? path.dirname(referrer) : moduleRoot;
+  const filename = isRelative(specifier)
+    ? path.resolve(base, specifier)
+    : path.join(moduleRoot, encodeURIComponent(specifier));
+  if (format === 'commonjs') {
+    sources.set(filename, resolved[1]);
+    return { format, filename };
+  }
+  return { format, filename, source: resolved[1] };
+};
+
+const resolveFilename = Module._resolveFilename;
+Module._resolveFilename = function (request, parent, ...args) {
+  if (sources.has(request)) {
+    return request;
+  }
+  const resolved = Module.isBuiltin(request)
+    ? undefined
+    : resolve(request, parent?.filename, false);
+  if (resolved === undefined) {
+    return resolveFilename.call(this, request, parent, ...args);
+  }
+  if (resolved.format === 'module') {
+    const err = new Error(`Cannot require the ES module ${request}, use import instead`);
+    err.code = 'ERR_REQUIRE_ESM';
+    throw err;
+  }
+  return resolved.filename;
+};
+const load = Module.prototype.load;
+Module.prototype.load = function (filename) {
+  if (!sources.has(filename)) {
+    return load.call(this, filename);
+  }
+  this.filename = filename;
+  this.paths = Module._nodeModulePaths(path.dirname(filename));
+  this._compile(sources.get(filename), filename);
+  this.loaded = true;
+};
+
+const { port1, port2 } = new (require('worker_threads').MessageChannel)();
+port1.on('message', ({ id, specifier, referrer }) => {
+  let reply;
+  try {
+    const filename = referrer?.startsWith('file:') ? url.fileURLToPath(referrer) : referrer;
+    reply = resolve(specifier, filename, true) ?? {};
+  } catch (err) {
+    reply = { error: err instanceof Error ? err.message : String(err) };
+  }
+  port1.postMessage({ id, ...reply });
+});
+port1.unref();
+Module.register('data:text/javascript,' + encodeURIComponent(resolverHooks), {
+  data: { port: port2 },
+  transferList: [port2],
+});
+";
+
+/// Module customization hooks that pass imports to the event loop thread to be resolved
+/// by the Rust resolver. They run on a separate thread.
+const RESOLVER_HOOKS: &str = "\
+import { isBuiltin } from 'node:module';
+import { pathToFileURL } from 'node:url';
+let port;
+let nextId = 0;
+const pending = new Map();
+const modules = new Map();
+export function initialize(data) {
+  port = data.port;
+  port.on('message', ({ id, ...reply }) => {
+    pending.get(id)(reply);
+    pending.delete(id);
+    if (pending.size === 0) {
+      port.unref();
+    }
+  });
+  port.unref();
+}
+const request = (specifier, referrer) =>
+  new Promise((resolve) => {
+    const id = nextId++;
+    pending.set(id, resolve);
+    port.ref();
+    port.postMessage({ id, specifier, referrer });
+  });
+export async function resolve(specifier, context, nextResolve) {
+  if (isBuiltin(specifier) || specifier.startsWith('data:')) {
+    return nextResolve(specifier, context);
+  }
+  const reply = await request(specifier, context.parentURL);
+  if (reply.error !== undefined) {
+    throw new Error(reply.error);
+  }
+  if (reply.format === undefined) {
+    return nextResolve(specifier, context);
+  }
+  const url = pathToFileURL(reply.filename).href;
+  if (reply.format === 'file') {
+    return nextResolve(url, context);
+  }
+  modules.set(url, reply);
+  return { url, format: reply.format, shortCircuit: true };
+}
+export async function load(url, context, nextLoad) {
+  const module = modules.get(url);
+  if (module === undefined) {
+    return nextLoad(url, context);
+  }
+  // Without a source, CommonJS modules are loaded by the CommonJS loader
+  // of the event loop thread, which has their source
+  return { format: module.format, source: module.source, shortCircuit: true };
+}
+";
+
+/// Lets `require` load the native modules registered as linked bindings.
+#[cfg(feature = "napi")]
+const NATIVE_MODULES: &str = "\
+{
+  const Module = require('module');
+  for (const name of nativeModules) {
+    if (Module.isBuiltin(name)) {
+      throw new Error(`The native module ${name} would replace a Node.js builtin module`);
+    }
+  }
+  const load = Module._load;
+  Module._load = function (request, ...args) {
+    return nativeModules.has(request)
+      ? process._linkedBinding(request)
+      : load.call(this, request, ...args);
+  };
+}
+";
+
+/// Defines the function used by `Runtime::import`. It is compiled as a CommonJS
+/// module, so relative specifiers are resolved against the module root.
+const IMPORT_FUNCTION: &str = "\
+{
+  const Module = require('module');
+  const filename = require('path').join(moduleRoot, '[nodejs]');
+  const importer = new Module(filename, null);
+  importer.filename = filename;
+  importer.paths = Module._nodeModulePaths(moduleRoot);
+  importer._compile('module.exports = (specifier) => import(specifier);', filename);
+  Object.defineProperty(globalThis, Symbol.for('nodejs.import'), { value: importer.exports });
+}
+";
+
+/// Module customization hooks that serve the source of inline ES modules.
+const ESM_SOURCE_HOOKS: &str = "\
+let modules;
+export function initialize(data) {
+  modules = new Map(data.modules);
+}
+export async function resolve(specifier, context, nextResolve) {
+  if (modules.has(specifier)) {
+    return { url: specifier, format: 'module', shortCircuit: true };
+  }
+  return nextResolve(specifier, context);
+}
+export async function load(url, context, nextLoad) {
+  if (modules.has(url)) {
+    return { format: 'module', source: modules.get(url), shortCircuit: true };
+  }
+  return nextLoad(url, context);
+}
+";
+
+/// Freezes an object and all objects reachable from it.
+const DEEP_FREEZE: &str = "\
+const deepFreeze = (value) => {
+  if (value !== null && typeof value === 'object' && !Object.isFrozen(value)) {
+    Object.freeze(value);
+    for (const key of Reflect.ownKeys(value)) {
+      deepFreeze(value[key]);
+    }
+  }
+  return value;
+};
+";
+
+/// Calls the N-API module init function passed to `node_run`.
+const LOAD_EMBEDDER_MODULE: &str = "process._linkedBinding('__embedder_mod');\n";
+
+/// Renders the source passed to `node::LoadEnvironment`.
+///
+/// The source is run as a function with the `process` object and a `require`
+/// function that can only load built-in modules in scope.
+pub(crate) fn main_script(args: &NodeArgs) -> String {
+    let mut script = String::from(REPORT_UNCAUGHT_EXCEPTIONS);
+    write_stdio_redirection(&mut script, args);
+    write_module_resolution(&mut script, args);
+    if args.module_resolver.is_some() {
+        let _ = write!(
+            script,
+            "{{\nconst resolverHooks = {};\n{MODULE_RESOLVER}}}\n",
+            js_string(RESOLVER_HOOKS)
+        );
+    }
+    #[cfg(feature = "napi")]
+    write_native_modules(&mut script, args);
+    script.push_str(IMPORT_FUNCTION);
+    if args.builtin_bootstrap || args.main_script.is_none() {
+        script.push_str(BUILTIN_BOOTSTRAP);
+    }
+
+    write_globals(&mut script, args);
+    script.push_str(LOAD_EMBEDDER_MODULE);
+    match &args.main_script {
+        None => {}
+        Some(MainScript::File(path)) => {
+            let _ = write!(
+                script,
+                "{{
+  const filename = require('path').resolve(moduleRoot, {path});
+  process.argv.splice(1, 0, filename);
+  require('module').runMain(filename);
+}}
+",
+                path = js_string(&path.to_string_lossy()),
+            );
+        }
+        Some(MainScript::Source { source, filename }) => {
+            let _ = write!(
+                script,
+                "{{
+  const Module = require('module');
+  const path = require('path');
+  const filename = path.resolve(moduleRoot, {filename});
+  process.argv.splice(1, 0, filename);
+  const mainModule = new Module(filename, null);
+  mainModule.id = '.';
+  mainModule.filename = filename;
+  mainModule.paths = Module._nodeModulePaths(path.dirname(filename));
+  process.mainModule = mainModule;
+  Module._cache[filename] = mainModule;
+  mainModule._compile({source}, filename);
+  mainModule.loaded = true;
+}}
+",
+                filename = js_string(filename),
+                source = js_string(source),
+            );
+        }
+        Some(MainScript::Module { source, filename }) => {
+            let _ = write!(
+                script,
+                "{{
+  const url = require('url');
+  const filename = require('path').resolve(moduleRoot, {filename});
+  const href = url.pathToFileURL(filename).href;
+  require('module').register('data:text/javascript,' + encodeURIComponent({hooks}), {{
+    data: {{ modules: [[href, {source}]] }},
+  }});
+  process.argv.splice(1, 0, filename);
+  globalThis[Symbol.for('nodejs.import')](href);
+}}
+",
+                filename = js_string(filename),
+                hooks = js_string(ESM_SOURCE_HOOKS),
+                source = js_string(source),
+            );
+        }
+    }
+
+    script
+}
+
+fn write_stdio_redirection(script: &mut String, args: &NodeArgs) {
+    if args.stdin.is_some() {
+        script.push_str(REDIRECT_INPUT);
+    }
+
+    if args.console_runtime.is_some() {
+        script.push_str(REDIRECT_CONSOLE);
+    }
+
+    if args.stdout.is_none() && args.stderr.is_none() {
+        return;
+    }
+
+    script.push_str(REDIRECT_OUTPUT);
+    if args.stdout.is_some() {
+        script.push_str("redirectOutput('stdout', 1);\n");
+    }
+    if args.stderr.is_some() {
+        script.push_str("redirectOutput('stderr', 2);\n");
+    }
+}
+
+fn write_module_resolution(script: &mut String, args: &NodeArgs) {
+    match &args.module_root {
+        Some(root) => {
+            let _ = writeln!(
+                script,
+                "const moduleRoot = require('path').resolve({});",
+                js_string(&root.to_string_lossy())
+            );
+        }
+        None => script.push_str("const moduleRoot = process.cwd();\n"),
+    }
+
+    if args.module_paths.is_empty() {
+        return;
+    }
+
+    let paths: Vec<String> = args
+        .module_paths
+        .iter()
+        .map(|path| js_string(&path.to_string_lossy()))
+        .collect();
+    let _ = writeln!(
+        script,
+        "const modulePaths = [{}].map((p) => require('path').resolve(moduleRoot, p));",
+        paths.join(", ")
+    );
+    script.push_str(MODULE_PATHS);
+}
+
+#[cfg(feature = "napi")]
+fn write_native_modules(script: &mut String, args: &NodeArgs) {
+    if args.native_modules.is_empty() {
+        return;
+    }
+
+    let names: Vec<String> = args
+        .native_modules
+        .iter()
+        .map(|module| js_string(module.name()))
+        .collect();
+    let _ = writeln!(
+        script,
+        "const nativeModules = new Set([{}]);",
+        names.join(", ")
+    );
+    script.push_str(NATIVE_MODULES);
+}
+
+fn write_globals(script: &mut String, args: &NodeArgs) {
+    if args.globals.is_empty() {
+        return;
+    }
+
+    script.push_str("{\n");
+    script.push_str(DEEP_FREEZE);
+
+    for (name, value) in &args.globals {
+        let writable = !value.is_frozen();
+        let _ = writeln!(
+            script,
+            "Object.defineProperty(globalThis, {name}, {{ value: {value}, writable: {writable}, \
+             enumerable: true, configurable: {writable} }});",
+            name = js_string(name),
+            value = value.to_js(),
+        );
+    }
+
+    script.push_str("}\n");
+}
+
+/// Quotes and escapes a string, so it can be embedded in JavaScript source.
+pub(crate) fn js_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{2028}' | '\u{2029}' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
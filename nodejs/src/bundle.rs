@@ -0,0 +1,281 @@
+//! JavaScript applications embedded in the binary, see [`Bundle`].
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::error::{ErrorKind, NodeError};
+
+/// The format written by [`crate::include_bundle`]: the magic bytes, a byte of flags
+/// and the files, deflated if [`FLAG_COMPRESSED`] is set. Each file is the length of its
+/// path as a little-endian `u32`, its path relative to the bundle, joined by `/`,
+/// the length of its content as a little-endian `u64` and its content.
+const MAGIC: &[u8; 8] = b"NODEJSB\x01";
+const FLAG_COMPRESSED: u8 = 1;
+
+/// Written to an extracted bundle last, with the SHA-256 of the bundle.
+const STAMP_FILE: &str = ".nodejs-bundle";
+
+/// A directory of JavaScript files and their `node_modules`, embedded in the binary
+/// using [`crate::include_bundle`].
+///
+/// Node.js loads modules from the file system, so a run using the bundle,
+/// see [`crate::args::NodeArgs::bundle`], extracts it first. It is extracted once per
+/// content to the cache directory of the user, and reused by later runs and processes.
+#[derive(Clone)]
+pub struct Bundle(Data);
+
+#[derive(Clone)]
+enum Data {
+    Static(&'static [u8]),
+    Owned(Arc<[u8]>),
+}
+
+impl Bundle {
+    /// Used by [`crate::include_bundle`]. The data is checked when the bundle is extracted.
+    #[doc(hidden)]
+    pub const fn from_static(data: &'static [u8]) -> Self {
+        Self(Data::Static(data))
+    }
+
+    /// Loads a bundle from the bytes returned by [`Bundle::as_bytes`].
+    pub fn from_bytes<B: Into<Vec<u8>>>(bytes: B) -> crate::Result<Self> {
+        let bytes = bytes.into();
+        files(&payload(&bytes)?)?;
+        Ok(Self(Data::Owned(bytes.into())))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            Data::Static(data) => data,
+            Data::Owned(data) => data,
+        }
+    }
+
+    /// Extracts the bundle to the cache directory unless it has been extracted before,
+    /// and returns the directory.
+    ///
+    /// The cache directory is `$XDG_CACHE_HOME/nodejs` or `~/.cache/nodejs` on Linux,
+    /// `~/Library/Caches/nodejs` on macOS and `%LOCALAPPDATA%\nodejs` on Windows.
+    /// On Unix, it is created only accessible by the user, and an existing directory
+    /// must be owned by them. A directory that was not completely extracted is extracted
+    /// again, but the files of a complete one are not checked. Delete the directory to
+    /// restore files that were changed.
+    pub fn extract(&self) -> crate::Result<PathBuf> {
+        // Unique per run, as several runs may extract the same bundle at once
+        static EXTRACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+        let hash = sha256_hex(self.as_bytes());
+        let name = format!("bundle-{hash}");
+        let cache_dir = cache_dir()?;
+        create_private_dir(&cache_dir)?;
+        let dir = cache_dir.join(&name);
+        if is_extracted(&dir, &hash) {
+            return Ok(dir);
+        }
+
+        let payload = payload(self.as_bytes())?;
+        let files = files(&payload)?;
+        if std::fs::symlink_metadata(&dir).is_ok() {
+            remove_all(&dir).map_err(|e| extract_error(&dir, e))?;
+        }
+
+        let partial = cache_dir.join(format!(
+            ".{name}-{}-{}",
+            std::process::id(),
+            EXTRACTIONS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = write_files(&partial, &files)
+            .and_then(|()| {
+                std::fs::write(partial.join(STAMP_FILE), &hash)
+                    .map_err(|e| extract_error(&partial, e))
+            })
+            .and_then(|()| std::fs::rename(&partial, &dir).map_err(|e| extract_error(&dir, e)));
+        match result {
+            Ok(()) => Ok(dir),
+            // Extracted by another run in the meantime
+            Err(_) if is_extracted(&dir, &hash) => {
+                let _ = std::fs::remove_dir_all(&partial);
+                Ok(dir)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&partial);
+                Err(e)
+            }
+        }
+    }
+
+    /// Extracts the files of the bundle to `dir`, replacing existing files.
+    pub fn extract_to<P: AsRef<Path>>(&self, dir: P) -> crate::Result<()> {
+        let payload = payload(self.as_bytes())?;
+        write_files(dir.as_ref(), &files(&payload)?)
+    }
+}
+
+impl std::fmt::Debug for Bundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bundle")
+            .field("len", &self.as_bytes().len())
+            .finish()
+    }
+}
+
+/// Returns the files of the bundle, inflated if needed.
+fn payload(data: &[u8]) -> crate::Result<Cow<'_, [u8]>> {
+    let Some(rest) = data.strip_prefix(MAGIC.as_slice()) else {
+        return Err(invalid_bundle(
+            "The data is not a bundle, or from another version",
+        ));
+    };
+
+    match rest.split_first() {
+        Some((&0, files)) => Ok(Cow::Borrowed(files)),
+        Some((&FLAG_COMPRESSED, files)) => miniz_oxide::inflate::decompress_to_vec(files)
+            .map(Cow::Owned)
+            .map_err(|e| invalid_bundle(format!("Failed to decompress the bundle: {e:?}"))),
+        _ => Err(invalid_bundle("The bundle has unknown flags")),
+    }
+}
+
+/// Splits the files of a bundle into their paths and contents.
+fn files(mut payload: &[u8]) -> crate::Result<Vec<(&str, &[u8])>> {
+    fn take<'a>(payload: &mut &'a [u8], len: usize) -> crate::Result<&'a [u8]> {
+        if payload.len() < len {
+            return Err(invalid_bundle("The bundle is truncated"));
+        }
+        let (taken, rest) = payload.split_at(len);
+        *payload = rest;
+        Ok(taken)
+    }
+
+    let mut files = Vec::new();
+    while !payload.is_empty() {
+        let name_len = u32::from_le_bytes(take(&mut payload, 4)?.try_into().unwrap());
+        let name = std::str::from_utf8(take(&mut payload, name_len as usize)?)
+            .map_err(|_| invalid_bundle("A path in the bundle is not valid UTF-8"))?;
+        // Paths must stay inside the directory the bundle is extracted to
+        let is_relative = !name.is_empty()
+            && !name.contains('\\')
+            && name.split('/').all(|part| {
+                let mut components = Path::new(part).components();
+                matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(_)), None)
+                )
+            });
+        if !is_relative {
+            return Err(invalid_bundle(format!(
+                "Invalid path in the bundle: {name:?}"
+            )));
+        }
+
+        let content_len = u64::from_le_bytes(take(&mut payload, 8)?.try_into().unwrap());
+        let content_len =
+            usize::try_from(content_len).map_err(|_| invalid_bundle("The bundle is truncated"))?;
+        files.push((name, take(&mut payload, content_len)?));
+    }
+
+    Ok(files)
+}
+
+fn write_files(dir: &Path, files: &[(&str, &[u8])]) -> crate::Result<()> {
+    for (name, content) in files {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| extract_error(dir, e))?;
+        }
+        std::fs::write(&path, content).map_err(|e| extract_error(dir, e))?;
+    }
+
+    // Empty bundles still have a directory
+    std::fs::create_dir_all(dir).map_err(|e| extract_error(dir, e))
+}
+
+/// Whether `dir` is a directory that the bundle with the hash was completely extracted to.
+fn is_extracted(dir: &Path, hash: &str) -> bool {
+    std::fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_dir())
+        && std::fs::read(dir.join(STAMP_FILE)).is_ok_and(|stamp| stamp == hash.as_bytes())
+}
+
+fn remove_all(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+fn cache_dir() -> crate::Result<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| Path::new(&home).join("Library").join("Caches"))
+    } else {
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+
+    // The temporary directory is shared with other users, so it is not used instead
+    base.map(|base| base.join("nodejs")).ok_or_else(|| {
+        NodeError::generic("Failed to extract the bundle: the user has no cache directory")
+    })
+}
+
+/// Creates the cache directory, which other users must not be able to change.
+fn create_private_dir(dir: &Path) -> crate::Result<()> {
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| extract_error(dir, e))?;
+    }
+
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    match builder.create(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => {
+            return Err(extract_error(dir, e))
+        }
+        _ => {}
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let metadata = std::fs::symlink_metadata(dir).map_err(|e| extract_error(dir, e))?;
+        if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } {
+            return Err(NodeError::generic(format!(
+                "Failed to extract the bundle: {} is not a directory owned by the user",
+                dir.display()
+            )));
+        }
+        if metadata.mode() & 0o077 != 0 {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+                .map_err(|e| extract_error(dir, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn invalid_bundle<T: ToString>(message: T) -> NodeError {
+    NodeError::from_kind(ErrorKind::InvalidBundle, message)
+}
+
+fn extract_error(dir: &Path, e: std::io::Error) -> NodeError {
+    NodeError::generic(format!(
+        "Failed to extract the bundle to {}: {e}",
+        dir.display()
+    ))
+    .with_source(e)
+}
@@ -0,0 +1,67 @@
+//! Routing the JavaScript console to `tracing`, see [`crate::args::NodeArgs::trace_console`].
+
+use std::ffi::{c_void, CStr};
+use std::panic::AssertUnwindSafe;
+
+use tracing::Level;
+
+use crate::sys;
+
+/// The console of a run, passed as the data of [`TracingConsole::callback`].
+pub(crate) struct TracingConsole {
+    runtime: String,
+}
+
+impl TracingConsole {
+    pub(crate) fn new(runtime: String) -> Self {
+        Self { runtime }
+    }
+
+    /// The `node_console_callback_t` that emits the events.
+    pub(crate) unsafe extern "C" fn callback(
+        data: *mut c_void,
+        message: *const sys::node_console_message_t,
+    ) {
+        let console = &*(data as *const TracingConsole);
+        let message = &*message;
+        // A panicking subscriber drops the event, unwinding into C++ would abort the process
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| console.emit(message)));
+    }
+
+    unsafe fn emit(&self, message: &sys::node_console_message_t) {
+        let method = CStr::from_ptr(message.method).to_string_lossy();
+        let text = if message.message_length == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(message.message as *const u8, message.message_length)
+        };
+        let text = String::from_utf8_lossy(text);
+        let args: Vec<_> = (0..message.argc.max(0) as usize)
+            .map(|i| CStr::from_ptr(*message.argv.add(i)).to_string_lossy())
+            .collect();
+
+        // The target must be a constant, runtimes are told apart by the `runtime` field
+        macro_rules! event {
+            ($level:expr) => {
+                tracing::event!(
+                    target: "nodejs::console",
+                    $level,
+                    runtime = %self.runtime,
+                    method = %method,
+                    args = ?args,
+                    "{}",
+                    text
+                )
+            };
+        }
+
+        // The level of an event must be a constant
+        match method.as_ref() {
+            "trace" => event!(Level::TRACE),
+            "debug" => event!(Level::DEBUG),
+            "warn" => event!(Level::WARN),
+            "error" => event!(Level::ERROR),
+            _ => event!(Level::INFO),
+        }
+    }
+}
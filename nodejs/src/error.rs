@@ -1,17 +1,155 @@
+/// The category of a [`NodeError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The instance handle is already used by another run.
+    AlreadyRunning,
+    /// An argument, option or environment variable is invalid,
+    /// for example because it contains a NUL character.
+    InvalidArgument,
+    /// Node.js or the environment failed to initialize.
+    InitFailed,
+    /// An exception was thrown while running the main script,
+    /// including the module init function.
+    BootstrapException,
+    /// An exception was not handled after the main script has run.
+    UncaughtException,
+    /// Node.js exited with a non-zero exit code, see [`NodeError::code`].
+    NonZeroExit,
+    /// The run was terminated while JavaScript may still have been executing,
+    /// see [`crate::InstanceHandle::terminate`].
+    Terminated,
+    /// The JavaScript heap reached its limit, see [`crate::args::NodeArgs::heap_limit`].
+    OutOfMemory,
+    /// The wall-clock time limit of the run or evaluation was exceeded,
+    /// see [`crate::limits::TimeLimits`].
+    TimeLimitExceeded,
+    /// The CPU time limit of the run or evaluation was exceeded,
+    /// see [`crate::limits::TimeLimits`].
+    CpuTimeLimitExceeded,
+    /// The snapshot was created by another Node.js build or is corrupted,
+    /// see [`crate::snapshot::Snapshot`].
+    InvalidSnapshot,
+    /// The bundle is corrupted or was embedded by another version of this crate,
+    /// see [`crate::bundle::Bundle`].
+    InvalidBundle,
+    /// Node.js failed to stop, or was not running.
+    StopFailed,
+    /// Node.js is not running, or stopped before the operation completed.
+    NotRunning,
+    /// A JavaScript exception was thrown by code run from Rust.
+    Exception,
+    /// A mutex was poisoned by a panicking thread.
+    LockPoisoned,
+    Other,
+}
+
+/// A JavaScript exception that was not handled, see [`NodeError::exception`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsException {
+    pub(crate) name: Option<String>,
+    pub(crate) message: Option<String>,
+    pub(crate) stack: Option<String>,
+    pub(crate) cause: Option<String>,
+}
+
+impl JsException {
+    /// The `name` of the error, such as `TypeError`.
+    /// `None` if the thrown value is not an error.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The `message` of the error, or the inspected value if it is not an error.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// The inspected `cause` of the error, including the causes of the cause.
+    pub fn cause(&self) -> Option<&str> {
+        self.cause.as_deref()
+    }
+}
+
+impl std::fmt::Display for JsException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.name, &self.message) {
+            (Some(name), Some(message)) => write!(f, "{name}: {message}"),
+            (Some(name), None) => write!(f, "{name}"),
+            (None, Some(message)) => write!(f, "{message}"),
+            (None, None) => write!(f, "Unknown JavaScript error"),
+        }
+    }
+}
+
 pub struct NodeError {
+    kind: ErrorKind,
     message: String,
     code: i32,
+    stack: Option<String>,
+    exception: Option<Box<JsException>>,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
 impl NodeError {
     pub fn new(message: String, code: i32) -> Self {
-        Self { message, code }
+        Self {
+            kind: ErrorKind::Other,
+            message,
+            code,
+            stack: None,
+            exception: None,
+            source: None,
+        }
+    }
+
+    /// Creates an error of the given kind with the exit code 1.
+    pub fn from_kind<T: ToString>(kind: ErrorKind, message: T) -> Self {
+        Self {
+            kind,
+            ..Self::generic(message)
+        }
+    }
+
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Sets the JavaScript stack trace of the error.
+    pub fn with_stack<T: ToString>(mut self, stack: T) -> Self {
+        self.stack = Some(stack.to_string());
+        self
+    }
+
+    /// Sets the uncaught JavaScript exception that failed the run, including its stack.
+    pub(crate) fn with_exception(mut self, exception: JsException) -> Self {
+        self.stack = exception.stack.clone();
+        self.exception = Some(Box::new(exception));
+        self
+    }
+
+    /// Sets the error that caused this error, returned by [`std::error::Error::source`].
+    pub fn with_source<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+        mut self,
+        source: E,
+    ) -> Self {
+        self.source = Some(source.into());
+        self
     }
 
     pub fn generic<T: ToString>(message: T) -> Self {
         Self::new(message.to_string(), 1)
     }
 
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
     pub fn message(&self) -> &str {
         &self.message
     }
@@ -19,13 +157,29 @@ impl NodeError {
     pub fn code(&self) -> i32 {
         self.code
     }
+
+    /// The stack trace of the JavaScript error this error was created from, if any.
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// The exception that failed the run, for errors of the kinds
+    /// [`ErrorKind::UncaughtException`] and [`ErrorKind::BootstrapException`].
+    /// Exceptions thrown before the main script has started are not reported.
+    pub fn exception(&self) -> Option<&JsException> {
+        self.exception.as_deref()
+    }
 }
 
 impl std::fmt::Debug for NodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("NodeError")
+            .field("kind", &self.kind)
             .field("message", &self.message)
             .field("code", &self.code)
+            .field("stack", &self.stack)
+            .field("exception", &self.exception)
+            .field("source", &self.source)
             .finish()
     }
 }
@@ -36,6 +190,12 @@ impl std::fmt::Display for NodeError {
     }
 }
 
-impl std::error::Error for NodeError {}
+impl std::error::Error for NodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
 
 pub type Result<T> = std::result::Result<T, NodeError>;
@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use crate::bootstrap::js_string;
+#[cfg(feature = "serde")]
+use crate::error::{ErrorKind, NodeError};
+
+/// A value defined on `globalThis` before any user code runs.
+/// See [`crate::args::NodeArgs::global`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalValue {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<GlobalValue>),
+    Object(Vec<(String, GlobalValue)>),
+    /// JSON text, parsed using `JSON.parse`.
+    /// Invalid JSON makes the bootstrap throw.
+    Json(String),
+    /// The wrapped value, deeply frozen using `Object.freeze`.
+    /// The global property itself is read-only as well.
+    Frozen(Box<GlobalValue>),
+}
+
+impl GlobalValue {
+    /// Wraps the value in [`GlobalValue::Frozen`].
+    pub fn frozen(self) -> Self {
+        match self {
+            Self::Frozen(_) => self,
+            value => Self::Frozen(Box::new(value)),
+        }
+    }
+
+    /// Serializes the value to JSON.
+    #[cfg(feature = "serde")]
+    pub fn from_serialize<T: serde::Serialize + ?Sized>(value: &T) -> crate::Result<Self> {
+        serde_json::to_string(value)
+            .map(Self::Json)
+            .map_err(|e| NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e))
+    }
+
+    pub(crate) fn is_frozen(&self) -> bool {
+        matches!(self, Self::Frozen(_))
+    }
+
+    /// Renders the value as a JavaScript expression.
+    /// Frozen values are passed to the `deepFreeze` function defined by the bootstrap.
+    pub(crate) fn to_js(&self) -> String {
+        let mut js = String::new();
+        self.write_js(&mut js);
+        js
+    }
+
+    fn write_js(&self, js: &mut String) {
+        match self {
+            Self::Undefined => js.push_str("undefined"),
+            Self::Null => js.push_str("null"),
+            Self::Bool(value) => {
+                let _ = write!(js, "{value}");
+            }
+            Self::Number(value) if value.is_nan() => js.push_str("NaN"),
+            Self::Number(value) if value.is_infinite() => js.push_str(if *value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }),
+            Self::Number(value) => {
+                let _ = write!(js, "{value}");
+            }
+            Self::String(value) => js.push_str(&js_string(value)),
+            Self::Array(values) => {
+                js.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        js.push_str(", ");
+                    }
+                    value.write_js(js);
+                }
+                js.push(']');
+            }
+            Self::Object(entries) => {
+                // Computed keys, so `__proto__` defines a property instead of the prototype
+                js.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        js.push_str(", ");
+                    }
+                    let _ = write!(js, "[{}]: ", js_string(key));
+                    value.write_js(js);
+                }
+                js.push('}');
+            }
+            Self::Json(json) => {
+                let _ = write!(js, "JSON.parse({})", js_string(json));
+            }
+            Self::Frozen(value) => {
+                js.push_str("deepFreeze(");
+                value.write_js(js);
+                js.push(')');
+            }
+        }
+    }
+}
+
+impl From<bool> for GlobalValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<f64> for GlobalValue {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<f32> for GlobalValue {
+    fn from(value: f32) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<i32> for GlobalValue {
+    fn from(value: i32) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<u32> for GlobalValue {
+    fn from(value: u32) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<&str> for GlobalValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for GlobalValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<GlobalValue>> From<Option<T>> for GlobalValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<GlobalValue>> From<Vec<T>> for GlobalValue {
+    fn from(values: Vec<T>) -> Self {
+        Self::Array(values.into_iter().map(Into::into).collect())
+    }
+}
@@ -0,0 +1,41 @@
+//! Limiting the JavaScript heap of a run, see [`crate::args::NodeArgs::heap_limit`].
+
+use std::ffi::c_void;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+type HeapLimitFn = dyn Fn(usize, usize) -> Option<usize> + Send + Sync;
+
+/// Decides whether a run may continue once its heap is full,
+/// see [`crate::args::NodeArgs::on_heap_limit`].
+#[derive(Clone)]
+pub(crate) struct HeapLimitHandler(Arc<HeapLimitFn>);
+
+impl HeapLimitHandler {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(usize, usize) -> Option<usize> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// The `node_heap_limit_callback_t` that calls the handler.
+    /// A panic stops the run like returning `None`.
+    pub(crate) unsafe extern "C" fn callback(
+        data: *mut c_void,
+        limit: usize,
+        used: usize,
+    ) -> usize {
+        let handler = &*(data as *const HeapLimitHandler);
+        match std::panic::catch_unwind(AssertUnwindSafe(|| (handler.0)(limit, used))) {
+            Ok(Some(new_limit)) => new_limit,
+            _ => 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for HeapLimitHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeapLimitHandler").finish_non_exhaustive()
+    }
+}
@@ -1,9 +1,27 @@
 #![doc = include_str!("../README.md")]
 
 pub mod args;
+mod bootstrap;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "tracing")]
+mod console;
 pub mod error;
+pub mod global;
+mod heap;
+pub mod limits;
+#[cfg(feature = "napi")]
+mod native;
+pub mod options;
 pub mod raw;
+pub mod resolve;
+#[cfg(feature = "napi")]
+pub mod runtime;
+pub mod snapshot;
+pub mod stdio;
 mod sys;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 
 use args::NodeArgs;
 #[cfg(feature = "napi")]
@@ -14,37 +32,41 @@ use neon::context::ModuleContext;
 use neon::result::NeonResult;
 
 pub use crate::error::Result;
+pub use crate::raw::{InstanceHandle, Shutdown};
+#[cfg(feature = "napi")]
+pub use crate::runtime::Runtime;
 #[cfg(feature = "neon")]
 pub use neon;
+#[cfg(feature = "bundle")]
+pub use nodejs_macros::include_bundle;
 
-static NODE_EXECUTED: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
-
-#[cfg(any(feature = "neon", feature = "napi"))]
-fn run_inner<F: FnOnce() -> Result<()>>(f: F) -> Result<()> {
-    let mut executed = NODE_EXECUTED
-        .lock()
-        .map_err(|_| error::NodeError::new("Mutex lock failed".to_string(), 1))?;
-
-    if *executed {
-        Err(error::NodeError::new(
-            "Node.js is already running".to_string(),
-            1,
-        ))
-    } else {
-        *executed = true;
-        f()
-    }
+#[cfg(feature = "neon")]
+pub fn run_neon<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
+    f: F,
+    args: Option<NodeArgs>,
+) -> Result<()> {
+    unsafe { raw::run_neon(f, args) }
 }
 
 #[cfg(feature = "neon")]
-pub fn run_neon<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
+pub fn run_neon_with_handle<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
     f: F,
     args: Option<NodeArgs>,
+    handle: &InstanceHandle,
 ) -> Result<()> {
-    run_inner(|| unsafe { raw::run_neon(f, args) })
+    unsafe { raw::run_neon_with_handle(f, args, Some(handle)) }
 }
 
 #[cfg(feature = "napi")]
 pub fn run_napi<F: FnOnce(Env) -> napi::Result<()>>(f: F, args: Option<NodeArgs>) -> Result<()> {
-    run_inner(|| unsafe { raw::run_napi(f, args) })
+    unsafe { raw::run_napi(f, args) }
+}
+
+#[cfg(feature = "napi")]
+pub fn run_napi_with_handle<F: FnOnce(Env) -> napi::Result<()>>(
+    f: F,
+    args: Option<NodeArgs>,
+    handle: &InstanceHandle,
+) -> Result<()> {
+    unsafe { raw::run_napi_with_handle(f, args, Some(handle)) }
 }
@@ -0,0 +1,68 @@
+//! Limiting the execution time of a run or of a single evaluation.
+
+use std::time::Duration;
+
+use crate::error::ErrorKind;
+use crate::sys;
+
+/// Wall-clock and CPU time budgets, see [`crate::args::NodeArgs::time_limits`] and
+/// [`crate::Runtime::eval_with_limits`].
+///
+/// The CPU time is the one used by the event loop thread. The limits are enforced by
+/// a watchdog thread, which terminates the JavaScript that is running once a budget is
+/// exceeded, even if it never yields to the event loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeLimits {
+    pub(crate) wall_time: Option<Duration>,
+    pub(crate) cpu_time: Option<Duration>,
+}
+
+impl TimeLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wall_time(mut self, limit: Duration) -> Self {
+        self.wall_time = Some(limit);
+        self
+    }
+
+    pub fn cpu_time(mut self, limit: Duration) -> Self {
+        self.cpu_time = Some(limit);
+        self
+    }
+
+    pub(crate) fn wall_time_ms(&self) -> u64 {
+        millis(self.wall_time)
+    }
+
+    pub(crate) fn cpu_time_ms(&self) -> u64 {
+        millis(self.cpu_time)
+    }
+}
+
+/// Rounds up, so a limit shorter than a millisecond is not ignored.
+fn millis(limit: Option<Duration>) -> u64 {
+    limit.map_or(0, |limit| {
+        limit
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .try_into()
+            .unwrap_or(u64::MAX)
+    })
+}
+
+/// The kind and message of the error for an exceeded limit reported by the embedding API.
+pub(crate) fn exceeded(limit: sys::node_time_limit_t) -> Option<(ErrorKind, &'static str)> {
+    match limit {
+        sys::node_time_limit_t_NODE_TIME_LIMIT_WALL => Some((
+            ErrorKind::TimeLimitExceeded,
+            "The wall-clock time limit was exceeded",
+        )),
+        sys::node_time_limit_t_NODE_TIME_LIMIT_CPU => Some((
+            ErrorKind::CpuTimeLimitExceeded,
+            "The CPU time limit was exceeded",
+        )),
+        _ => None,
+    }
+}
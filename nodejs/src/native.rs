@@ -0,0 +1,126 @@
+//! Native modules implemented in Rust, see [`crate::args::NodeArgs::native_module`].
+
+use std::ffi::{c_void, CString};
+use std::os::raw::c_int;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use napi::sys::{napi_env, napi_value};
+use napi::{Env, JsError, JsObject, NapiValue};
+
+use crate::error::{ErrorKind, NodeError};
+use crate::sys;
+
+type InitFn = dyn Fn(Env, JsObject) -> napi::Result<()> + Send + Sync;
+
+/// An N-API module registered under its own name.
+#[derive(Clone)]
+pub(crate) struct NativeModule {
+    name: String,
+    init: Arc<InitFn>,
+}
+
+impl NativeModule {
+    pub(crate) fn new<F>(name: String, init: F) -> Self
+    where
+        F: Fn(Env, JsObject) -> napi::Result<()> + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            init: Arc::new(init),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `node_module_init_t` that calls the init function with the exports.
+    /// An error or a panic is thrown to the JavaScript that loads the module.
+    unsafe extern "C" fn callback(
+        data: *mut c_void,
+        env: *mut c_void,
+        exports: *mut c_void,
+    ) -> *mut c_void {
+        let module = &*(data as *const NativeModule);
+        let env = Env::from_raw(env as napi_env);
+        let exports_object = JsObject::from_raw_unchecked(env.raw(), exports as napi_value);
+
+        match std::panic::catch_unwind(AssertUnwindSafe(|| (module.init)(env, exports_object))) {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                let _ = env.throw(JsError::from(err).into_unknown(env));
+            }
+            Err(_) => {
+                let _ = env.throw_error(
+                    &format!("The native module {} panicked while loading", module.name),
+                    None,
+                );
+            }
+        }
+
+        exports
+    }
+}
+
+impl std::fmt::Debug for NativeModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeModule")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The modules passed to `node_run`. They point into the names and the modules,
+/// which must outlive the run.
+pub(crate) struct CModules {
+    _names: Vec<CString>,
+    modules: Vec<sys::node_module_t>,
+}
+
+impl CModules {
+    pub(crate) fn new(modules: &[NativeModule]) -> crate::Result<Self> {
+        let names = modules
+            .iter()
+            .map(|module| {
+                // Used by the embedding layer itself, or by Node.js for its builtin modules.
+                // Other builtin names are rejected by the main script.
+                if module.name.is_empty()
+                    || module.name.starts_with("__embedder")
+                    || module.name.starts_with("node:")
+                {
+                    return Err(NodeError::from_kind(
+                        ErrorKind::InvalidArgument,
+                        format!("Invalid native module name: {:?}", module.name),
+                    ));
+                }
+                CString::new(module.name.as_str()).map_err(|e| {
+                    NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e)
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let modules = names
+            .iter()
+            .zip(modules)
+            .map(|(name, module)| sys::node_module_t {
+                name: name.as_ptr(),
+                init: Some(NativeModule::callback),
+                data: module as *const NativeModule as *mut c_void,
+            })
+            .collect();
+
+        Ok(Self {
+            _names: names,
+            modules,
+        })
+    }
+
+    pub(crate) fn count(&self) -> c_int {
+        self.modules.len() as c_int
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const sys::node_module_t {
+        self.modules.as_ptr()
+    }
+}
@@ -0,0 +1,404 @@
+use std::ffi::CString;
+use std::os::raw::c_int;
+
+use crate::raw::take_c_string;
+use crate::sys;
+
+/// How deprecation warnings are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deprecations {
+    /// Silences deprecation warnings (`--no-deprecation`).
+    Silent,
+    /// Prints a stack trace for deprecations (`--trace-deprecation`).
+    Trace,
+    /// Throws errors for deprecations (`--throw-deprecation`).
+    Throw,
+    /// Also emits pending deprecation warnings (`--pending-deprecation`).
+    Pending,
+}
+
+/// How unhandled promise rejections are handled (`--unhandled-rejections`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnhandledRejections {
+    Throw,
+    Strict,
+    Warn,
+    WarnWithErrorCode,
+    None,
+}
+
+impl UnhandledRejections {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Throw => "throw",
+            Self::Strict => "strict",
+            Self::Warn => "warn",
+            Self::WarnWithErrorCode => "warn-with-error-code",
+            Self::None => "none",
+        }
+    }
+}
+
+/// Experimental Node.js features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Experimental {
+    /// `--experimental-vm-modules`
+    VmModules,
+    /// `--experimental-wasm-modules`
+    WasmModules,
+    /// `--experimental-import-meta-resolve`
+    ImportMetaResolve,
+    /// `--experimental-network-imports`
+    NetworkImports,
+    /// `--experimental-detect-module`
+    DetectModule,
+    /// `--experimental-permission`
+    Permission,
+}
+
+impl Experimental {
+    fn flag(&self) -> &'static str {
+        match self {
+            Self::VmModules => "--experimental-vm-modules",
+            Self::WasmModules => "--experimental-wasm-modules",
+            Self::ImportMetaResolve => "--experimental-import-meta-resolve",
+            Self::NetworkImports => "--experimental-network-imports",
+            Self::DetectModule => "--experimental-detect-module",
+            Self::Permission => "--experimental-permission",
+        }
+    }
+}
+
+/// Options that make Node.js do something other than running the embedder's code.
+const UNSUPPORTED_OPTIONS: &[&str] = &[
+    "-c",
+    "--check",
+    "--completion-bash",
+    "--build-snapshot",
+    "-e",
+    "--eval",
+    "-h",
+    "--help",
+    "-i",
+    "--interactive",
+    "-p",
+    "--print",
+    "--prof-process",
+    "--test",
+    "--v8-options",
+    "-v",
+    "--version",
+    "--watch",
+    "--watch-path",
+];
+
+/// An error returned if [`NodeOptions`] or the exec arguments are invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionsError {
+    /// The argument is not an option.
+    InvalidOption(String),
+    /// The option is neither known to Node.js nor to V8.
+    UnknownOption(String),
+    /// Node.js rejected the options, for example because a value is missing.
+    Rejected(String),
+    /// The option is valid for the `node` executable, but not when embedding Node.js.
+    UnsupportedOption(String),
+    /// Both options set the same value.
+    ConflictingOptions(String, String),
+    /// The value of the option is out of range.
+    InvalidValue { option: String, reason: String },
+}
+
+impl std::fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOption(option) => write!(f, "{option} is not an option"),
+            Self::UnknownOption(option) => write!(f, "{option} is not a Node.js or V8 option"),
+            Self::Rejected(errors) => write!(f, "Invalid Node.js options: {errors}"),
+            Self::UnsupportedOption(option) => {
+                write!(f, "{option} is not supported when embedding Node.js")
+            }
+            Self::ConflictingOptions(first, second) => {
+                write!(f, "{first} conflicts with {second}")
+            }
+            Self::InvalidValue { option, reason } => {
+                write!(f, "Invalid value for {option}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+/// Typed Node.js and V8 options.
+/// They are passed to Node.js as exec arguments, see [`crate::args::NodeArgs::options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeOptions {
+    max_old_space_size: Option<u64>,
+    max_semi_space_size: Option<u64>,
+    stack_size: Option<u64>,
+    warnings: Option<bool>,
+    trace_warnings: bool,
+    deprecations: Option<Deprecations>,
+    unhandled_rejections: Option<UnhandledRejections>,
+    source_maps: bool,
+    experimental: Vec<Experimental>,
+    disallow_code_generation_from_strings: bool,
+    v8_flags: Vec<String>,
+}
+
+impl NodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the size of the old generation of the V8 heap in MiB (`--max-old-space-size`).
+    ///
+    /// V8 options are set by the first run in a process, later runs have to pass the same value.
+    pub fn max_old_space_size(mut self, mib: u64) -> Self {
+        self.max_old_space_size = Some(mib);
+        self
+    }
+
+    /// Sets the size of a semi-space of the V8 heap in MiB (`--max-semi-space-size`).
+    ///
+    /// V8 options are set by the first run in a process, later runs have to pass the same value.
+    pub fn max_semi_space_size(mut self, mib: u64) -> Self {
+        self.max_semi_space_size = Some(mib);
+        self
+    }
+
+    /// Sets the V8 stack size in KiB (`--stack-size`).
+    /// The value must be smaller than the stack of the thread Node.js runs on.
+    ///
+    /// V8 options are set by the first run in a process, later runs have to pass the same value.
+    pub fn stack_size(mut self, kib: u64) -> Self {
+        self.stack_size = Some(kib);
+        self
+    }
+
+    /// Whether process warnings are emitted. Disabling them passes `--no-warnings`.
+    pub fn warnings(mut self, warnings: bool) -> Self {
+        self.warnings = Some(warnings);
+        self
+    }
+
+    /// Prints stack traces for process warnings (`--trace-warnings`).
+    pub fn trace_warnings(mut self, trace_warnings: bool) -> Self {
+        self.trace_warnings = trace_warnings;
+        self
+    }
+
+    pub fn deprecations(mut self, deprecations: Deprecations) -> Self {
+        self.deprecations = Some(deprecations);
+        self
+    }
+
+    pub fn unhandled_rejections(mut self, unhandled_rejections: UnhandledRejections) -> Self {
+        self.unhandled_rejections = Some(unhandled_rejections);
+        self
+    }
+
+    /// Enables source map support for stack traces (`--enable-source-maps`).
+    pub fn source_maps(mut self, source_maps: bool) -> Self {
+        self.source_maps = source_maps;
+        self
+    }
+
+    /// Enables an experimental feature.
+    pub fn experimental(mut self, feature: Experimental) -> Self {
+        if !self.experimental.contains(&feature) {
+            self.experimental.push(feature);
+        }
+        self
+    }
+
+    /// Makes `eval` and `new Function` throw (`--disallow-code-generation-from-strings`).
+    ///
+    /// V8 options are set by the first run in a process, later runs have to pass the same value.
+    pub fn disallow_code_generation_from_strings(mut self, disallow: bool) -> Self {
+        self.disallow_code_generation_from_strings = disallow;
+        self
+    }
+
+    /// Adds a V8 flag that has no typed equivalent, such as `--expose-gc`.
+    ///
+    /// V8 options are set by the first run in a process, later runs have to pass the same value.
+    pub fn v8_flag<S: ToString>(mut self, flag: S) -> Self {
+        self.v8_flags.push(flag.to_string());
+        self
+    }
+
+    /// Validates the options and converts them to exec arguments.
+    pub fn to_args(&self) -> Result<Vec<String>, OptionsError> {
+        let mut args = Vec::new();
+
+        let sizes = [
+            ("--max-old-space-size", self.max_old_space_size),
+            ("--max-semi-space-size", self.max_semi_space_size),
+            ("--stack-size", self.stack_size),
+        ];
+        for (option, value) in sizes {
+            match value {
+                Some(0) => {
+                    return Err(OptionsError::InvalidValue {
+                        option: option.to_string(),
+                        reason: "must be greater than 0".to_string(),
+                    })
+                }
+                Some(value) => args.push(format!("{option}={value}")),
+                None => {}
+            }
+        }
+
+        if self.warnings == Some(false) {
+            if self.trace_warnings {
+                return Err(OptionsError::ConflictingOptions(
+                    "--no-warnings".to_string(),
+                    "--trace-warnings".to_string(),
+                ));
+            }
+
+            args.push("--no-warnings".to_string());
+        }
+
+        if self.trace_warnings {
+            args.push("--trace-warnings".to_string());
+        }
+
+        match self.deprecations {
+            Some(Deprecations::Silent) => args.push("--no-deprecation".to_string()),
+            Some(Deprecations::Trace) => args.push("--trace-deprecation".to_string()),
+            Some(Deprecations::Throw) => args.push("--throw-deprecation".to_string()),
+            Some(Deprecations::Pending) => args.push("--pending-deprecation".to_string()),
+            None => {}
+        }
+
+        if let Some(unhandled_rejections) = self.unhandled_rejections {
+            args.push(format!(
+                "--unhandled-rejections={}",
+                unhandled_rejections.as_str()
+            ));
+        }
+
+        if self.source_maps {
+            args.push("--enable-source-maps".to_string());
+        }
+
+        args.extend(self.experimental.iter().map(|f| f.flag().to_string()));
+
+        if self.disallow_code_generation_from_strings {
+            args.push("--disallow-code-generation-from-strings".to_string());
+        }
+
+        for flag in &self.v8_flags {
+            validate_raw_option(flag)?;
+            args.push(flag.clone());
+        }
+
+        check_conflicts(&args)?;
+        check_known_options(&args)?;
+        Ok(args)
+    }
+}
+
+/// Combines the typed options with the raw exec arguments.
+pub(crate) fn merge_exec_args(
+    options: &NodeOptions,
+    exec_args: &[String],
+) -> Result<Vec<String>, OptionsError> {
+    let mut args = options.to_args()?;
+    for arg in exec_args {
+        validate_raw_option(arg)?;
+        args.push(arg.clone());
+    }
+
+    check_conflicts(&args)?;
+    check_known_options(&args)?;
+    Ok(args)
+}
+
+fn option_name(arg: &str) -> &str {
+    arg.split_once('=').map_or(arg, |(name, _)| name)
+}
+
+fn validate_raw_option(arg: &str) -> Result<(), OptionsError> {
+    if !arg.starts_with('-') || arg == "-" || arg == "--" {
+        return Err(OptionsError::InvalidOption(arg.to_string()));
+    }
+
+    let name = option_name(arg);
+    if UNSUPPORTED_OPTIONS.contains(&name) {
+        return Err(OptionsError::UnsupportedOption(name.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Parses the options like Node.js does, without applying them. Options that Node.js
+/// passes to V8 are checked against the flags of the V8 that libnode was built with.
+fn check_known_options(args: &[String]) -> Result<(), OptionsError> {
+    let c_args = args
+        .iter()
+        .map(|arg| CString::new(arg.as_str()).map_err(|_| OptionsError::InvalidOption(arg.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let argv = c_args.iter().map(|arg| arg.as_ptr()).collect::<Vec<_>>();
+
+    let result = unsafe { sys::node_check_options(argv.len() as c_int, argv.as_ptr()) };
+    let error = unsafe { take_c_string(result.error) };
+    let v8_args = unsafe { take_c_string(result.v8_args) };
+    if let Some(error) = error {
+        return Err(OptionsError::Rejected(error));
+    }
+
+    for arg in v8_args.iter().flat_map(|v8_args| v8_args.lines()) {
+        // V8 accepts underscores in place of dashes, and negates flags with `no`
+        let name = option_name(arg).trim_start_matches('-').replace('_', "-");
+        let flag = name
+            .strip_prefix("no")
+            .map(|flag| flag.trim_start_matches('-'));
+        let is_known = |name: &str| sys::V8_FLAGS.contains(&name);
+        if !is_known(&name) && !flag.is_some_and(is_known) {
+            return Err(OptionsError::UnknownOption(option_name(arg).to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns an error if two options set the same value.
+/// Only the settings covered by [`NodeOptions`] are checked, other options may be repeated.
+fn check_conflicts(args: &[String]) -> Result<(), OptionsError> {
+    // Options that configure the same setting, or contradict each other, grouped together
+    const GROUPS: &[&[&str]] = &[
+        &["--max-old-space-size"],
+        &["--max-semi-space-size"],
+        &["--stack-size"],
+        &["--no-warnings", "--trace-warnings"],
+        &["--no-deprecation", "--trace-deprecation"],
+        &["--no-deprecation", "--throw-deprecation"],
+        &["--unhandled-rejections"],
+    ];
+
+    // V8 accepts underscores in place of dashes
+    let name = |arg: &str| option_name(arg).replace('_', "-");
+    let conflict = |first: &str, second: &str| {
+        let (first, second) = (name(first), name(second));
+        GROUPS
+            .iter()
+            .any(|group| group.contains(&first.as_str()) && group.contains(&second.as_str()))
+    };
+
+    for (i, first) in args.iter().enumerate() {
+        for second in &args[i + 1..] {
+            if first != second && conflict(first, second) {
+                return Err(OptionsError::ConflictingOptions(
+                    first.clone(),
+                    second.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -13,73 +13,478 @@ use neon::result::NeonResult;
 
 #[cfg(feature = "napi")]
 use napi::JsError;
-use std::ffi::{CStr, CString};
+#[cfg(any(feature = "neon", feature = "napi"))]
+use std::cell::Cell;
+use std::ffi::{c_void, CStr, CString};
 use std::os::raw::{c_char, c_int};
+use std::ptr::{null, null_mut, NonNull};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::args::NodeArgs;
-use crate::error::NodeError;
+use crate::error::{ErrorKind, JsException, NodeError};
+use crate::limits::TimeLimits;
+use crate::snapshot::Snapshot;
 use crate::sys;
 
+#[cfg(any(feature = "neon", feature = "napi"))]
+thread_local! {
+    /// The module init function of the Node.js instance starting on the current thread.
+    static MODULE_INIT_FN: Cell<*mut c_void> = const { Cell::new(null_mut()) }; // *mut Option<F>
+}
+
+/// A handle to a Node.js instance, used to stop it independently of
+/// other instances running in the same process.
+///
+/// A handle can be reused for several consecutive runs, but only by one run at a time.
+pub struct InstanceHandle {
+    instance: NonNull<sys::node_instance_t>,
+    running: AtomicBool,
+}
+
+// The instance state is guarded by a mutex on the C++ side.
+unsafe impl Send for InstanceHandle {}
+unsafe impl Sync for InstanceHandle {}
+
+impl InstanceHandle {
+    pub fn new() -> Self {
+        let instance = unsafe { sys::node_instance_create() };
+        Self {
+            instance: NonNull::new(instance).expect("Failed to create a Node.js instance"),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Stops the Node.js instance running on this handle, interrupting the JavaScript
+    /// that is currently executing, even if it never yields to the event loop.
+    /// Returns an error if the instance is not running.
+    pub fn stop(&self) -> crate::Result<()> {
+        stop_result(unsafe { sys::node_instance_stop(self.instance.as_ptr()) })
+    }
+
+    /// Stops the Node.js instance running on this handle like [`InstanceHandle::stop`].
+    /// The only difference is that the run returns an error of the kind
+    /// [`ErrorKind::Terminated`].
+    /// Returns an error if the instance is not running.
+    pub fn terminate(&self) -> crate::Result<()> {
+        stop_result(unsafe { sys::node_instance_terminate(self.instance.as_ptr()) })
+    }
+
+    /// Stops the Node.js instance running on this handle gracefully.
+    ///
+    /// Emits the `shutdown` event on `process`, so the script can close its servers and
+    /// clear its timers, and blocks until the event loop has no more work to do.
+    /// `beforeExit` and `exit` are emitted as usual then. If the run has not ended
+    /// after `timeout`, it is terminated like [`InstanceHandle::terminate`].
+    /// Returns an error if the instance is not running.
+    pub fn shutdown(&self, timeout: Duration) -> crate::Result<Shutdown> {
+        shutdown_result(unsafe {
+            sys::node_instance_shutdown(self.instance.as_ptr(), timeout_millis(timeout))
+        })
+    }
+}
+
+/// How a run ended after a graceful shutdown was requested,
+/// see [`InstanceHandle::shutdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// The event loop ran out of work before the timeout.
+    Graceful,
+    /// The run was terminated after the timeout, and returns an error of the kind
+    /// [`ErrorKind::Terminated`].
+    Forced,
+}
+
+impl InstanceHandle {
+    /// Limits the time of the JavaScript called until [`InstanceHandle::end_time_limits`].
+    /// Must be called on the event loop thread of the running instance.
+    pub(crate) fn begin_time_limits(&self, limits: &TimeLimits) -> crate::Result<()> {
+        let code = unsafe {
+            sys::node_instance_begin_time_limit(
+                self.instance.as_ptr(),
+                limits.wall_time_ms(),
+                limits.cpu_time_ms(),
+            )
+        };
+        if code != 0 {
+            return Err(NodeError::from_kind(
+                ErrorKind::NotRunning,
+                "Node.js is not running",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the error for the limit that was exceeded since
+    /// [`InstanceHandle::begin_time_limits`], if any.
+    pub(crate) fn end_time_limits(&self) -> crate::Result<()> {
+        let limit = unsafe { sys::node_instance_end_time_limit(self.instance.as_ptr()) };
+        match crate::limits::exceeded(limit) {
+            Some((kind, message)) => Err(NodeError::from_kind(kind, message)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for InstanceHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InstanceHandle {
+    fn drop(&mut self) {
+        unsafe { sys::node_instance_destroy(self.instance.as_ptr()) }
+    }
+}
+
 /// Starts a Node.js instance and immediately run the provided N-API module init function.
 /// Blocks until the event loop stops, and returns the exit code.
 ///
 /// # Safety
-/// This function can only be called at most once.
-pub unsafe fn run_raw(
-    napi_reg_func: *mut std::os::raw::c_void,
+/// `napi_reg_func` must be a valid `napi_addon_register_func`.
+pub unsafe fn run_raw(napi_reg_func: *mut c_void, args: Option<NodeArgs>) -> crate::Result<()> {
+    run_raw_with_handle(napi_reg_func, args, None)
+}
+
+/// Same as [`run_raw`], but the instance can be stopped using the provided handle.
+/// Returns an error if the handle is already used by another run.
+///
+/// # Safety
+/// `napi_reg_func` must be a valid `napi_addon_register_func`.
+pub unsafe fn run_raw_with_handle(
+    napi_reg_func: *mut c_void,
     args: Option<NodeArgs>,
+    handle: Option<&InstanceHandle>,
 ) -> crate::Result<()> {
-    let node_args = args.unwrap_or_default();
-    let args = node_args.get_args()?;
+    if let Some(handle) = handle {
+        if handle.running.swap(true, Ordering::SeqCst) {
+            return Err(NodeError::from_kind(
+                ErrorKind::AlreadyRunning,
+                "Node.js is already running",
+            ));
+        }
+    }
 
-    if args.is_empty() {
-        return Err(NodeError::generic(
-            "Node.js requires at least one argument".to_string(),
-        ));
+    let result = run_node(napi_reg_func, args, handle);
+    if let Some(handle) = handle {
+        handle.running.store(false, Ordering::SeqCst);
     }
 
-    let args: Vec<CString> = args
-        .into_iter()
-        .map(|arg| CString::new(arg).map_err(|e| NodeError::generic(e.to_string())))
-        .collect::<crate::Result<_>>()?;
+    result
+}
 
-    let mut argc_c = Vec::<*const c_char>::new();
-    for arg in &args {
-        argc_c.push(arg.as_ptr() as *const c_char)
-    }
+unsafe fn run_node(
+    napi_reg_func: *mut c_void,
+    args: Option<NodeArgs>,
+    handle: Option<&InstanceHandle>,
+) -> crate::Result<()> {
+    let node_args = args.unwrap_or_default();
+    #[cfg(feature = "bundle")]
+    let node_args = node_args.extract_bundle()?;
+    let c_args = CArgs::new(&node_args)?;
+
+    let outputs = crate::stdio::Outputs::new(&node_args);
+    #[cfg(feature = "tracing")]
+    let console = node_args
+        .console_runtime
+        .clone()
+        .map(crate::console::TracingConsole::new);
+    #[cfg(feature = "tracing")]
+    let (console_callback, console_data): (sys::node_console_callback_t, _) = match &console {
+        Some(console) => (
+            Some(crate::console::TracingConsole::callback),
+            console as *const _ as *mut c_void,
+        ),
+        None => (None, null_mut()),
+    };
+    #[cfg(not(feature = "tracing"))]
+    let (console_callback, console_data) = (None, null_mut());
+
+    let main_script = CString::new(crate::bootstrap::main_script(&node_args))
+        .map_err(|e| NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e))?;
+
+    let snapshot = node_args.snapshot.as_ref().map(Snapshot::as_bytes);
+    #[cfg(feature = "napi")]
+    let modules = crate::native::CModules::new(&node_args.native_modules)?;
+    #[cfg(feature = "napi")]
+    let (module_count, modules) = (modules.count(), modules.as_ptr());
+    #[cfg(not(feature = "napi"))]
+    let (module_count, modules) = (0, null());
+
+    // Released by node_run, possibly after it has returned, so nothing may fail
+    // between here and the call
+    let input = node_args
+        .stdin
+        .clone()
+        .map(crate::stdio::InputSource::into_raw);
 
     let result = sys::node_run(sys::node_options_t {
-        process_argc: argc_c.len() as c_int,
-        process_argv: argc_c.as_ptr(),
         napi_reg_func,
+        instance: handle.map_or(null_mut(), |handle| handle.instance.as_ptr()),
+        module_count,
+        modules,
+        main_script: main_script.as_ptr(),
+        output_callback: (!outputs.is_empty()).then_some(crate::stdio::Outputs::callback),
+        output_data: &outputs as *const _ as *mut c_void,
+        input_callback: input
+            .is_some()
+            .then_some(crate::stdio::InputSource::callback),
+        input_unread: input.is_some().then_some(crate::stdio::InputSource::unread),
+        input_release: input
+            .is_some()
+            .then_some(crate::stdio::InputSource::release),
+        input_data: input.unwrap_or(null_mut()),
+        console_callback,
+        console_data,
+        resolve_callback: node_args
+            .module_resolver
+            .is_some()
+            .then_some(crate::resolve::ModuleResolver::callback),
+        resolve_data: node_args
+            .module_resolver
+            .as_ref()
+            .map_or(null_mut(), |resolver| resolver as *const _ as *mut c_void),
+        heap_limit: node_args.heap_limit,
+        heap_limit_callback: node_args
+            .heap_limit_handler
+            .is_some()
+            .then_some(crate::heap::HeapLimitHandler::callback),
+        heap_limit_data: node_args
+            .heap_limit_handler
+            .as_ref()
+            .map_or(null_mut(), |handler| handler as *const _ as *mut c_void),
+        time_limit_ms: node_args.time_limits.wall_time_ms(),
+        cpu_time_limit_ms: node_args.time_limits.cpu_time_ms(),
+        snapshot_data: snapshot.map_or(null(), |snapshot| snapshot.as_ptr() as *const c_char),
+        snapshot_length: snapshot.map_or(0, <[u8]>::len),
+        ..c_args.options()
     });
 
-    if !result.error.is_null() {
-        let result_error_string = CString::from(CStr::from_ptr(result.error));
-        libc::free(result.error as _);
+    let error_message = take_c_string(result.error);
+    let exception = JsException {
+        name: take_c_string(result.exception.name),
+        message: take_c_string(result.exception.message),
+        stack: take_c_string(result.exception.stack),
+        cause: take_c_string(result.exception.cause),
+    };
 
-        Err(NodeError::new(
-            result_error_string.to_string_lossy().into_owned(),
-            result.exit_code as i32,
-        ))
-    } else if result.exit_code != 0 {
-        Err(NodeError::new(
-            "Node.js exited with a non-zero exit code".to_string(),
-            result.exit_code as i32,
-        ))
+    let (kind, default_message) = match result.status {
+        sys::node_run_status_t_NODE_RUN_INIT_FAILED => {
+            (ErrorKind::InitFailed, "Node.js failed to initialize")
+        }
+        sys::node_run_status_t_NODE_RUN_INVALID_OPTIONS => (
+            ErrorKind::InvalidArgument,
+            "The Node.js options are invalid",
+        ),
+        sys::node_run_status_t_NODE_RUN_BOOTSTRAP_EXCEPTION => (
+            ErrorKind::BootstrapException,
+            "An exception was thrown while running the main script",
+        ),
+        sys::node_run_status_t_NODE_RUN_UNCAUGHT_EXCEPTION => {
+            (ErrorKind::UncaughtException, "Uncaught exception")
+        }
+        sys::node_run_status_t_NODE_RUN_TERMINATED => {
+            (ErrorKind::Terminated, "Node.js was terminated")
+        }
+        sys::node_run_status_t_NODE_RUN_OUT_OF_MEMORY => {
+            (ErrorKind::OutOfMemory, "JavaScript heap out of memory")
+        }
+        sys::node_run_status_t_NODE_RUN_INVALID_SNAPSHOT => {
+            (ErrorKind::InvalidSnapshot, "The snapshot can't be used")
+        }
+        sys::node_run_status_t_NODE_RUN_TIME_LIMIT_EXCEEDED => {
+            crate::limits::exceeded(result.time_limit)
+                .unwrap_or((ErrorKind::TimeLimitExceeded, "A time limit was exceeded"))
+        }
+        _ if result.exit_code != 0 => (
+            ErrorKind::NonZeroExit,
+            "Node.js exited with a non-zero exit code",
+        ),
+        _ if error_message.is_some() => (ErrorKind::Other, ""),
+        _ => return Ok(()),
+    };
+
+    let reported = exception != JsException::default();
+    let message = match error_message {
+        Some(message) => message,
+        None if reported => format!("Uncaught {exception}"),
+        None => default_message.to_string(),
+    };
+
+    let error = NodeError::new(message, result.exit_code as i32).with_kind(kind);
+    if reported {
+        Err(error.with_exception(exception))
     } else {
-        Ok(())
+        Err(error)
     }
 }
 
-/// Stops the running Node.js instance.
+/// Runs `script` and returns the snapshot of the environment, see [`Snapshot::create`].
+pub(crate) fn create_snapshot(script: String, args: Option<NodeArgs>) -> crate::Result<Vec<u8>> {
+    let node_args = args.unwrap_or_default();
+    let c_args = CArgs::new(&node_args)?;
+    let script = CString::new(script)
+        .map_err(|e| NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e))?;
+
+    let result = unsafe {
+        sys::node_create_snapshot(sys::node_options_t {
+            main_script: script.as_ptr(),
+            ..c_args.options()
+        })
+    };
+
+    let error_message = unsafe { take_c_string(result.error) };
+    if result.data.is_null() {
+        let message = error_message.unwrap_or_else(|| "Failed to create the snapshot".to_string());
+        return Err(NodeError::new(message, result.exit_code as i32));
+    }
+
+    let data = unsafe { std::slice::from_raw_parts(result.data as *const u8, result.length) };
+    let data = data.to_vec();
+    unsafe { libc::free(result.data as _) };
+    Ok(data)
+}
+
+/// Returns the reason why the snapshot can't be used by the linked Node.js, if any.
+pub(crate) fn check_snapshot(data: &[u8]) -> Option<String> {
+    unsafe {
+        take_c_string(sys::node_check_snapshot(
+            data.as_ptr() as *const c_char,
+            data.len(),
+        ))
+    }
+}
+
+/// The arguments and environment variables of [`NodeArgs`] as C strings.
+struct CArgs {
+    // Owns the strings the pointers below point into
+    _strings: Vec<CString>,
+    args_c: Vec<*const c_char>,
+    exec_args_c: Vec<*const c_char>,
+    script_args_c: Vec<*const c_char>,
+    env_vars_c: Option<Vec<*const c_char>>,
+}
+
+impl CArgs {
+    fn new(node_args: &NodeArgs) -> crate::Result<Self> {
+        let args = node_args.get_args()?;
+        if args.is_empty() {
+            return Err(NodeError::from_kind(
+                ErrorKind::InvalidArgument,
+                "Node.js requires at least one argument",
+            ));
+        }
+
+        let args = to_c_strings(args)?;
+        let exec_args = to_c_strings(node_args.get_exec_args()?)?;
+        let script_args = to_c_strings(node_args.script_args.clone())?;
+        let env_vars = node_args.get_env_vars()?.map(to_c_strings).transpose()?;
+        Ok(Self {
+            args_c: to_c_ptrs(&args),
+            exec_args_c: to_c_ptrs(&exec_args),
+            script_args_c: to_c_ptrs(&script_args),
+            env_vars_c: env_vars.as_deref().map(to_c_ptrs),
+            // Moving the strings doesn't move their contents
+            _strings: [args, exec_args, script_args, env_vars.unwrap_or_default()]
+                .into_iter()
+                .flatten()
+                .collect(),
+        })
+    }
+
+    /// Options with the arguments set and everything else unset.
+    /// They must not be used after `self` is dropped.
+    fn options(&self) -> sys::node_options_t {
+        sys::node_options_t {
+            process_argc: self.args_c.len() as c_int,
+            process_argv: self.args_c.as_ptr(),
+            exec_argc: self.exec_args_c.len() as c_int,
+            exec_argv: self.exec_args_c.as_ptr(),
+            script_argc: self.script_args_c.len() as c_int,
+            script_argv: self.script_args_c.as_ptr(),
+            env_count: self
+                .env_vars_c
+                .as_ref()
+                .map_or(0, |vars| vars.len() as c_int),
+            env_vars: self
+                .env_vars_c
+                .as_ref()
+                .map_or(null(), |vars| vars.as_ptr()),
+            // The remaining fields are pointers, numbers and optional callbacks
+            ..unsafe { std::mem::zeroed() }
+        }
+    }
+}
+
+/// Copies and frees a string allocated by the embedding API.
+pub(crate) unsafe fn take_c_string(ptr: *mut c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    let string = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    libc::free(ptr as _);
+    Some(string)
+}
+
+fn to_c_strings(args: Vec<String>) -> crate::Result<Vec<CString>> {
+    args.into_iter()
+        .map(|arg| {
+            CString::new(arg)
+                .map_err(|e| NodeError::from_kind(ErrorKind::InvalidArgument, &e).with_source(e))
+        })
+        .collect()
+}
+
+fn to_c_ptrs(args: &[CString]) -> Vec<*const c_char> {
+    args.iter().map(|arg| arg.as_ptr()).collect()
+}
+
+/// Stops all running Node.js instances.
 /// Returns an error if Node.js is not running.
 /// Returns Ok(()) if Node.js is stopped successfully.
 ///
 /// # Safety
 /// This function should be safe as long as it is called after [`run_raw`] or [`run_neon`].
 pub unsafe fn stop() -> crate::Result<()> {
-    let code = sys::node_stop();
+    stop_result(sys::node_stop())
+}
+
+/// Stops all running Node.js instances like [`stop`], but the runs return an error
+/// of the kind [`ErrorKind::Terminated`], see [`InstanceHandle::terminate`].
+/// Returns an error if Node.js is not running.
+///
+/// # Safety
+/// This function should be safe as long as it is called after [`run_raw`] or [`run_neon`].
+pub unsafe fn terminate() -> crate::Result<()> {
+    stop_result(sys::node_terminate())
+}
+
+/// Stops all running Node.js instances gracefully, see [`InstanceHandle::shutdown`].
+/// The instances share the timeout. Returns [`Shutdown::Forced`] if any of them
+/// was terminated, and an error if Node.js is not running.
+///
+/// # Safety
+/// This function should be safe as long as it is called after [`run_raw`] or [`run_neon`].
+pub unsafe fn shutdown(timeout: Duration) -> crate::Result<Shutdown> {
+    shutdown_result(sys::node_shutdown(timeout_millis(timeout)))
+}
+
+fn timeout_millis(timeout: Duration) -> c_int {
+    timeout.as_millis().min(c_int::MAX as u128) as c_int
+}
+
+fn shutdown_result(code: c_int) -> crate::Result<Shutdown> {
+    match code {
+        0 => Ok(Shutdown::Graceful),
+        1 => Ok(Shutdown::Forced),
+        code => stop_result(code).map(|()| Shutdown::Graceful),
+    }
+}
+
+fn stop_result(code: c_int) -> crate::Result<()> {
     if code != 0 {
         let error_str = if code == -1 {
             ": Node.js is not running"
@@ -87,10 +492,10 @@ pub unsafe fn stop() -> crate::Result<()> {
             ""
         };
 
-        Err(NodeError::new(
-            format!("Node.js failed to stop{error_str}"),
-            code as i32,
-        ))
+        Err(
+            NodeError::new(format!("Node.js failed to stop{error_str}"), code as i32)
+                .with_kind(ErrorKind::StopFailed),
+        )
     } else {
         Ok(())
     }
@@ -100,70 +505,92 @@ pub unsafe fn stop() -> crate::Result<()> {
 /// Blocks until the event loop stops, and returns the exit code.
 ///
 /// # Safety
-/// This function can only be called at most once.
+/// This function is safe as long as the exports of the module are not
+/// accessed after the instance has stopped.
 #[cfg(feature = "neon")]
 pub unsafe fn run_neon<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
     f: F,
     args: Option<NodeArgs>,
 ) -> crate::Result<()> {
-    use std::ptr::null_mut;
-    use std::sync::Once;
-    static mut MODULE_INIT_FN: *mut std::ffi::c_void = null_mut(); // *mut Option<F>
+    run_neon_with_handle(f, args, None)
+}
 
+/// Same as [`run_neon`], but the instance can be stopped using the provided handle.
+///
+/// # Safety
+/// See [`run_neon`].
+#[cfg(feature = "neon")]
+pub unsafe fn run_neon_with_handle<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
+    f: F,
+    args: Option<NodeArgs>,
+    handle: Option<&InstanceHandle>,
+) -> crate::Result<()> {
     let mut module_init_fn = Some(f);
-    MODULE_INIT_FN = (&mut module_init_fn) as *mut Option<F> as _;
+    MODULE_INIT_FN.with(|init_fn| init_fn.set((&mut module_init_fn) as *mut Option<F> as _));
 
     unsafe extern "C" fn napi_reg_func<F: for<'a> FnOnce(ModuleContext<'a>) -> NeonResult<()>>(
         env: neon::macro_internal::runtime::raw::Env,
         m: neon::macro_internal::runtime::raw::Local,
     ) -> neon::macro_internal::runtime::raw::Local {
         neon::macro_internal::initialize_module(env, std::mem::transmute(m), |ctx| {
-            static ONCE: Once = Once::new();
-            let mut result = Ok(());
-            ONCE.call_once(|| {
-                let module_init_fn = (MODULE_INIT_FN as *mut Option<F>).as_mut().unwrap();
-                let module_init_fn = module_init_fn.take().unwrap();
-                MODULE_INIT_FN = null_mut();
-                result = module_init_fn(ctx)
-            });
-            result
+            let module_init_fn = MODULE_INIT_FN.with(|init_fn| init_fn.replace(null_mut()));
+            match (module_init_fn as *mut Option<F>)
+                .as_mut()
+                .and_then(Option::take)
+            {
+                Some(module_init_fn) => module_init_fn(ctx),
+                None => Ok(()),
+            }
         });
         m
     }
 
-    run_raw(napi_reg_func::<F> as _, args)
+    let result = run_raw_with_handle(napi_reg_func::<F> as _, args, handle);
+    MODULE_INIT_FN.with(|init_fn| init_fn.set(null_mut()));
+    result
 }
 
 /// Starts a Node.js instance and immediately run the provided N-API module init function.
 /// Blocks until the event loop stops, and returns the exit code.
 ///
 /// # Safety
-/// This function can only be called at most once.
+/// This function is safe as long as the [`Env`] is not used after the instance has stopped.
 #[cfg(feature = "napi")]
 pub unsafe fn run_napi<F: FnOnce(Env) -> napi::Result<()>>(
     f: F,
     args: Option<NodeArgs>,
 ) -> crate::Result<()> {
-    use std::ptr::null_mut;
-    use std::sync::Once;
-
-    static mut MODULE_INIT_FN: *mut std::os::raw::c_void = null_mut(); // *mut Option<F>
+    run_napi_with_handle(f, args, None)
+}
 
+/// Same as [`run_napi`], but the instance can be stopped using the provided handle.
+///
+/// # Safety
+/// See [`run_napi`].
+#[cfg(feature = "napi")]
+pub unsafe fn run_napi_with_handle<F: FnOnce(Env) -> napi::Result<()>>(
+    f: F,
+    args: Option<NodeArgs>,
+    handle: Option<&InstanceHandle>,
+) -> crate::Result<()> {
     let mut module_init_fn = Some(f);
-    MODULE_INIT_FN = (&mut module_init_fn) as *mut Option<F> as _;
+    MODULE_INIT_FN.with(|init_fn| init_fn.set((&mut module_init_fn) as *mut Option<F> as _));
 
     unsafe extern "C" fn napi_reg_func<F: FnOnce(Env) -> napi::Result<()>>(
         env: napi_env,
         exports: napi_value,
     ) -> napi_value {
-        static ONCE: Once = Once::new();
-        ONCE.call_once(|| {
-            napi_register_module_v1(env, exports);
-        });
+        napi_register_module_v1(env, exports);
+
+        let module_init_fn = MODULE_INIT_FN.with(|init_fn| init_fn.replace(null_mut()));
+        let module_init_fn = match (module_init_fn as *mut Option<F>)
+            .as_mut()
+            .and_then(Option::take)
+        {
+            Some(module_init_fn) => module_init_fn,
+            None => return exports,
+        };
 
-        let module_init_fn = (MODULE_INIT_FN as *mut Option<F>).as_mut().unwrap();
-        let module_init_fn = module_init_fn.take().unwrap();
-        MODULE_INIT_FN = null_mut();
         let env = Env::from_raw(env);
         let res = module_init_fn(env);
         res.unwrap_or_else(|err| {
@@ -173,5 +600,7 @@ pub unsafe fn run_napi<F: FnOnce(Env) -> napi::Result<()>>(
         exports
     }
 
-    run_raw(napi_reg_func::<F> as _, args)
+    let result = run_raw_with_handle(napi_reg_func::<F> as _, args, handle);
+    MODULE_INIT_FN.with(|init_fn| init_fn.set(null_mut()));
+    result
 }
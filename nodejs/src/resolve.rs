@@ -0,0 +1,131 @@
+//! Resolving modules in Rust, see [`crate::args::NodeArgs::module_resolver`].
+
+use std::ffi::{c_void, CStr};
+use std::os::raw::{c_char, c_int};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::sys;
+
+/// Whether a module is loaded by `require` or `import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResolveKind {
+    Require,
+    Import,
+}
+
+/// A module specifier passed to `require` or `import`.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolveRequest<'a> {
+    specifier: &'a str,
+    referrer: Option<&'a str>,
+    kind: ResolveKind,
+}
+
+impl<'a> ResolveRequest<'a> {
+    /// The specifier as written in JavaScript, such as `app:config` or `./util`.
+    pub fn specifier(&self) -> &'a str {
+        self.specifier
+    }
+
+    /// The filename of the module that loads the specifier, or its URL if it
+    /// is not a file. `None` for the main script and `Runtime::import`.
+    pub fn referrer(&self) -> Option<&'a str> {
+        self.referrer
+    }
+
+    pub fn kind(&self) -> ResolveKind {
+        self.kind
+    }
+}
+
+/// A module returned by a resolver.
+///
+/// Modules created from source get a virtual filename: relative specifiers are
+/// resolved against the directory of the referrer, other specifiers become a file
+/// in the module root. Relative `require` calls and imports in the source are
+/// resolved against it, and each filename is only evaluated once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedModule {
+    /// Loads the file at the path, which is resolved against the module root.
+    File(PathBuf),
+    /// A CommonJS module with the given source.
+    CommonJs(String),
+    /// An ES module with the given source. It can only be imported.
+    Module(String),
+}
+
+type ResolveFn = dyn Fn(&ResolveRequest<'_>) -> crate::Result<Option<ResolvedModule>> + Send + Sync;
+
+/// Resolves `require` and `import` before Node.js does,
+/// see [`crate::args::NodeArgs::module_resolver`].
+#[derive(Clone)]
+pub(crate) struct ModuleResolver(Arc<ResolveFn>);
+
+impl ModuleResolver {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(&ResolveRequest<'_>) -> crate::Result<Option<ResolvedModule>> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// The `node_resolve_callback_t` that calls the resolver.
+    /// An error or a panic is thrown as a JavaScript error.
+    pub(crate) unsafe extern "C" fn callback(
+        data: *mut c_void,
+        specifier: *const c_char,
+        referrer: *const c_char,
+        is_import: c_int,
+        set_value: sys::node_resolve_value_t,
+        target: *mut c_void,
+    ) -> sys::node_resolve_type_t {
+        let resolver = &*(data as *const ModuleResolver);
+        let specifier = CStr::from_ptr(specifier).to_string_lossy();
+        let referrer = (!referrer.is_null()).then(|| CStr::from_ptr(referrer).to_string_lossy());
+        let request = ResolveRequest {
+            specifier: &specifier,
+            referrer: referrer.as_deref(),
+            kind: if is_import != 0 {
+                ResolveKind::Import
+            } else {
+                ResolveKind::Require
+            },
+        };
+
+        let (resolve_type, value) =
+            match std::panic::catch_unwind(AssertUnwindSafe(|| (resolver.0)(&request))) {
+                Ok(Ok(None)) => return sys::node_resolve_type_t_NODE_RESOLVE_DEFAULT,
+                Ok(Ok(Some(ResolvedModule::File(path)))) => (
+                    sys::node_resolve_type_t_NODE_RESOLVE_FILE,
+                    path.to_string_lossy().into_owned(),
+                ),
+                Ok(Ok(Some(ResolvedModule::CommonJs(source)))) => {
+                    (sys::node_resolve_type_t_NODE_RESOLVE_COMMONJS, source)
+                }
+                Ok(Ok(Some(ResolvedModule::Module(source)))) => {
+                    (sys::node_resolve_type_t_NODE_RESOLVE_MODULE, source)
+                }
+                Ok(Err(err)) => (
+                    sys::node_resolve_type_t_NODE_RESOLVE_ERROR,
+                    err.message().to_string(),
+                ),
+                Err(_) => (
+                    sys::node_resolve_type_t_NODE_RESOLVE_ERROR,
+                    format!("The module resolver panicked while resolving {specifier:?}"),
+                ),
+            };
+
+        if let Some(set_value) = set_value {
+            set_value(target, value.as_ptr() as *const c_char, value.len());
+        }
+        resolve_type
+    }
+}
+
+impl std::fmt::Debug for ModuleResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModuleResolver").finish_non_exhaustive()
+    }
+}
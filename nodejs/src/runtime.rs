@@ -0,0 +1,760 @@
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::ptr::{null, null_mut, NonNull};
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{JoinHandle, ThreadId};
+use std::time::Duration;
+
+use napi::bindgen_prelude::FromNapiValue;
+use napi::sys::{napi_callback_info, napi_env, napi_ref, napi_threadsafe_function, napi_value};
+use napi::{Env, JsFunction, JsObject, JsUnknown, NapiRaw, NapiValue};
+
+use crate::args::NodeArgs;
+use crate::bootstrap::js_string;
+use crate::error::{ErrorKind, NodeError};
+use crate::limits::TimeLimits;
+use crate::raw::{InstanceHandle, Shutdown};
+
+type Task = Box<dyn FnOnce(Env) + Send>;
+
+/// Called with the value of a settled promise, or its rejection reason as `Err`.
+type Settled = Box<dyn FnOnce(Env, std::result::Result<napi_value, napi_value>)>;
+
+/// The thread-safe function used to post tasks to the event loop thread.
+struct TaskQueue(NonNull<napi::sys::napi_threadsafe_function__>);
+
+// N-API allows calling and releasing thread-safe functions from any thread.
+unsafe impl Send for TaskQueue {}
+
+impl TaskQueue {
+    fn raw(&self) -> napi_threadsafe_function {
+        self.0.as_ptr()
+    }
+}
+
+/// The task queue shared between the [`Runtime`] handle and the event loop thread.
+/// Set to `None` once the thread-safe function has been released or finalized.
+type SharedQueue = Arc<Mutex<Option<TaskQueue>>>;
+
+/// Posts tasks to the event loop thread.
+#[derive(Clone)]
+struct TaskSender {
+    queue: SharedQueue,
+    thread_id: ThreadId,
+}
+
+impl TaskSender {
+    fn exec<F, T>(&self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(Env) -> napi::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        if std::thread::current().id() == self.thread_id {
+            return Err(NodeError::from_kind(
+                ErrorKind::InvalidArgument,
+                "Runtime::exec cannot be called from the event loop thread",
+            ));
+        }
+
+        let (result_tx, result_rx) = mpsc::channel();
+        let task: Task = Box::new(move |env| {
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(env)))
+                .unwrap_or_else(|_| Err(napi::Error::from_reason("The task panicked")))
+                .map_err(|err| to_error(env, err));
+
+            let _ = result_tx.send(result);
+        });
+
+        self.post(task)?;
+        result_rx.recv().map_err(|_| {
+            NodeError::from_kind(
+                ErrorKind::NotRunning,
+                "Node.js stopped before the task was executed",
+            )
+        })?
+    }
+
+    fn post(&self, task: Task) -> crate::Result<()> {
+        let queue = self
+            .queue
+            .lock()
+            .map_err(|_| NodeError::from_kind(ErrorKind::LockPoisoned, "Mutex lock failed"))?;
+        let queue = queue
+            .as_ref()
+            .ok_or_else(|| NodeError::from_kind(ErrorKind::NotRunning, "Node.js is not running"))?;
+
+        let data = Box::into_raw(Box::new(task));
+        let status = unsafe {
+            napi::sys::napi_call_threadsafe_function(
+                queue.raw(),
+                data as *mut c_void,
+                napi::sys::ThreadsafeFunctionCallMode::nonblocking,
+            )
+        };
+
+        if status != napi::sys::Status::napi_ok {
+            drop(unsafe { Box::from_raw(data) });
+            return Err(NodeError::from_kind(
+                ErrorKind::NotRunning,
+                "Node.js is not running",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Releases the reference on the event loop. Later tasks are rejected.
+    fn release(&self) {
+        if let Ok(mut queue) = self.queue.lock() {
+            if let Some(queue) = queue.take() {
+                unsafe {
+                    napi::sys::napi_release_threadsafe_function(
+                        queue.raw(),
+                        napi::sys::ThreadsafeFunctionReleaseMode::release,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A strong reference to a JavaScript value.
+/// It can be moved to any thread, but only used on the event loop thread.
+#[derive(Clone, Copy)]
+struct Reference(napi_ref);
+
+unsafe impl Send for Reference {}
+
+impl Reference {
+    fn new(env: Env, value: napi_value) -> crate::Result<Self> {
+        let mut reference = null_mut();
+        let status =
+            unsafe { napi::sys::napi_create_reference(env.raw(), value, 1, &mut reference) };
+        if status != napi::sys::Status::napi_ok {
+            return Err(NodeError::generic("Failed to create a reference"));
+        }
+
+        Ok(Self(reference))
+    }
+
+    fn value(&self, env: Env) -> napi::Result<napi_value> {
+        let mut value = null_mut();
+        let status = unsafe { napi::sys::napi_get_reference_value(env.raw(), self.0, &mut value) };
+        if status != napi::sys::Status::napi_ok || value.is_null() {
+            return Err(napi::Error::from_reason(
+                "The referenced value is no longer alive",
+            ));
+        }
+
+        Ok(value)
+    }
+
+    fn delete(self, env: Env) {
+        unsafe { napi::sys::napi_delete_reference(env.raw(), self.0) };
+    }
+}
+
+/// A Node.js instance running on a dedicated background thread.
+/// Several runtimes can run at the same time, each with its own isolate and event loop.
+///
+/// The runtime keeps its event loop alive until [`Runtime::join`] is called,
+/// the instance is stopped using [`Runtime::stop`] or the script exits the process.
+/// Dropping the runtime without joining it stops the instance like [`Runtime::stop`],
+/// without waiting for the thread to finish.
+/// Scripts and closures can be submitted from any thread and are executed on the
+/// event loop thread.
+///
+/// Calling [`Runtime::eval`] or [`Runtime::exec`] from the event loop thread itself
+/// returns an error, as it would otherwise deadlock.
+pub struct Runtime {
+    tasks: TaskSender,
+    handle: Arc<InstanceHandle>,
+    thread: Option<JoinHandle<crate::Result<()>>>,
+}
+
+impl Runtime {
+    /// Starts a Node.js instance on a new thread.
+    /// Returns once the instance is ready to accept tasks, or the error
+    /// if the instance stopped before that.
+    pub fn spawn(args: NodeArgs) -> crate::Result<Self> {
+        let queue = SharedQueue::default();
+        let handle = Arc::new(InstanceHandle::new());
+        let (ready_tx, ready_rx) = mpsc::channel::<()>();
+
+        let thread_queue = queue.clone();
+        let thread_handle = handle.clone();
+        let thread = std::thread::Builder::new()
+            .name("nodejs".to_string())
+            .spawn(move || {
+                crate::run_napi_with_handle(
+                    move |env| {
+                        let tsfn = unsafe { create_task_queue(env, &thread_queue)? };
+                        *thread_queue
+                            .lock()
+                            .map_err(|_| napi::Error::from_reason("Mutex lock failed"))? =
+                            Some(tsfn);
+
+                        let _ = ready_tx.send(());
+                        Ok(())
+                    },
+                    Some(args),
+                    &thread_handle,
+                )
+            })
+            .map_err(|e| {
+                NodeError::from_kind(ErrorKind::InitFailed, "Failed to spawn the Node.js thread")
+                    .with_source(e)
+            })?;
+
+        if ready_rx.recv().is_err() {
+            return match thread.join() {
+                Ok(Err(err)) => Err(err),
+                Ok(Ok(())) => Err(NodeError::from_kind(
+                    ErrorKind::NotRunning,
+                    "Node.js stopped before the runtime was ready",
+                )),
+                Err(_) => Err(NodeError::generic("The Node.js thread panicked")),
+            };
+        }
+
+        Ok(Self {
+            tasks: TaskSender {
+                queue,
+                thread_id: thread.thread().id(),
+            },
+            handle,
+            thread: Some(thread),
+        })
+    }
+
+    /// Runs the provided closure on the event loop thread and returns its result.
+    /// Blocks until the closure has been executed.
+    pub fn exec<F, T>(&self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(Env) -> napi::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.tasks.exec(f)
+    }
+
+    /// Evaluates the provided script on the event loop thread and returns
+    /// its completion value converted to `T`.
+    pub fn eval<T, S>(&self, script: S) -> crate::Result<T>
+    where
+        T: FromNapiValue + Send + 'static,
+        S: Into<String>,
+    {
+        let script = script.into();
+        self.exec(move |env| env.run_script(script))
+    }
+
+    /// Same as [`Runtime::eval`], but the evaluation is terminated once it exceeds
+    /// one of the limits, and returns an error of the kind [`ErrorKind::TimeLimitExceeded`]
+    /// or [`ErrorKind::CpuTimeLimitExceeded`]. The runtime keeps running afterwards.
+    ///
+    /// Only the synchronous part of the script is limited, not the callbacks and
+    /// promises it schedules.
+    pub fn eval_with_limits<T, S>(&self, script: S, limits: TimeLimits) -> crate::Result<T>
+    where
+        T: FromNapiValue + Send + 'static,
+        S: Into<String>,
+    {
+        let script = script.into();
+        let handle = self.handle.clone();
+        self.exec(move |env| {
+            if let Err(err) = handle.begin_time_limits(&limits) {
+                return Ok(Err(err));
+            }
+            let result = env.run_script(script);
+            Ok(match handle.end_time_limits() {
+                Err(err) => {
+                    // The terminated script leaves an exception pending
+                    take_exception(env);
+                    Err(err)
+                }
+                Ok(()) => result.map_err(|err| to_error(env, err)),
+            })
+        })?
+    }
+
+    /// Evaluates the provided script on the event loop thread. If its completion value
+    /// is a promise, the returned future resolves once the promise settles, with the
+    /// fulfilled value converted to `T` or the rejection reason as the error.
+    ///
+    /// The script is submitted immediately and runs even if the future is not polled.
+    /// The future does not depend on a specific async runtime, but must not be
+    /// blocked on from the event loop thread.
+    pub fn eval_async<T, S>(&self, script: S) -> EvalFuture<T>
+    where
+        T: FromNapiValue + Send + 'static,
+        S: Into<String>,
+    {
+        let script = script.into();
+        let (future, completer) = EvalFuture::new();
+        let task: Task = Box::new(move |env| {
+            let value = match env.run_script::<_, JsUnknown>(script) {
+                Ok(value) => unsafe { value.raw() },
+                Err(err) => return completer.complete(Err(to_error(env, err))),
+            };
+
+            let mut is_promise = false;
+            unsafe { napi::sys::napi_is_promise(env.raw(), value, &mut is_promise) };
+            if !is_promise {
+                return completer.complete(from_value(env, value));
+            }
+
+            let settled: Settled = Box::new(move |env, result| {
+                completer.complete(match result {
+                    Ok(value) => from_value(env, value),
+                    Err(reason) => Err(error_from_value(env, reason)),
+                });
+            });
+            let promise = unsafe { JsObject::from_raw_unchecked(env.raw(), value) };
+            unsafe { on_settled(env, promise, settled) };
+        });
+
+        match self.tasks.post(task) {
+            Ok(()) => future,
+            Err(err) => EvalFuture::ready(Err(err)),
+        }
+    }
+
+    /// Imports a module like `import()`. The returned future resolves to its namespace
+    /// object once the module has been evaluated, like [`Runtime::eval_async`].
+    /// Relative specifiers are resolved against [`NodeArgs::module_root`].
+    ///
+    /// Errors thrown while loading or evaluating the module are returned
+    /// with their JavaScript stack, see [`NodeError::stack`].
+    pub fn import<S: Into<String>>(&self, specifier: S) -> EvalFuture<ModuleNamespace> {
+        let specifier = specifier.into();
+        let tasks = self.tasks.clone();
+        let (future, completer) = EvalFuture::new();
+        let task: Task = Box::new(move |env| {
+            let promise = match env.run_script::<_, JsObject>(format!(
+                "globalThis[Symbol.for('nodejs.import')]({})",
+                js_string(&specifier)
+            )) {
+                Ok(promise) => promise,
+                Err(err) => return completer.complete(Err(to_error(env, err))),
+            };
+
+            let settled: Settled = Box::new(move |env, result| {
+                completer.complete(match result {
+                    Ok(namespace) => Reference::new(env, namespace)
+                        .map(|reference| ModuleNamespace { reference, tasks }),
+                    Err(reason) => Err(error_from_value(env, reason)),
+                });
+            });
+            unsafe { on_settled(env, promise, settled) };
+        });
+
+        match self.tasks.post(task) {
+            Ok(()) => future,
+            Err(err) => EvalFuture::ready(Err(err)),
+        }
+    }
+
+    /// Stops the Node.js instance, interrupting the JavaScript that is currently executing,
+    /// such as an endless loop. Other runtimes are not affected.
+    /// Pending tasks are discarded and [`Runtime::join`] returns the result of the run.
+    pub fn stop(&self) -> crate::Result<()> {
+        self.handle.stop()
+    }
+
+    /// Stops the Node.js instance like [`Runtime::stop`]. The only difference is that
+    /// [`Runtime::join`] then returns an error of the kind [`ErrorKind::Terminated`].
+    pub fn terminate(&self) -> crate::Result<()> {
+        self.handle.terminate()
+    }
+
+    /// Stops accepting tasks and stops the Node.js instance gracefully,
+    /// see [`InstanceHandle::shutdown`]. Pending tasks are still executed.
+    /// Returns how the run ended, or the error of the run if it failed otherwise.
+    pub fn shutdown(self, timeout: Duration) -> crate::Result<Shutdown> {
+        self.tasks.release();
+        let shutdown = self.handle.shutdown(timeout);
+        let result = self.join();
+        match shutdown {
+            Ok(Shutdown::Forced) => Ok(Shutdown::Forced),
+            // The run has also ended if the instance was not running anymore
+            _ => result.map(|()| Shutdown::Graceful),
+        }
+    }
+
+    /// Stops accepting tasks and waits until the event loop has no more work to do.
+    /// Returns the same result as [`crate::raw::run_raw`].
+    pub fn join(mut self) -> crate::Result<()> {
+        self.tasks.release();
+        self.thread
+            .take()
+            .ok_or_else(|| {
+                NodeError::from_kind(
+                    ErrorKind::InvalidArgument,
+                    "The Node.js thread has already been joined",
+                )
+            })?
+            .join()
+            .map_err(|_| NodeError::generic("The Node.js thread panicked"))?
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Runtime {
+    /// Same as [`Runtime::spawn`], without blocking the async runtime.
+    pub async fn spawn_async(args: NodeArgs) -> crate::Result<Self> {
+        ::tokio::task::spawn_blocking(move || Self::spawn(args))
+            .await
+            .map_err(|e| NodeError::generic(e.to_string()).with_source(e))?
+    }
+
+    /// Same as [`Runtime::join`], without blocking the async runtime.
+    pub async fn join_async(self) -> crate::Result<()> {
+        ::tokio::task::spawn_blocking(move || self.join())
+            .await
+            .map_err(|e| NodeError::generic(e.to_string()).with_source(e))?
+    }
+
+    /// Defines a global function that runs `f` on the current tokio runtime and
+    /// returns a promise, see [`crate::tokio::create_async_function`].
+    /// Must be called from within a tokio runtime. Blocks until the function is defined.
+    pub fn define_async_fn<A, R, F, Fut>(&self, name: &str, f: F) -> crate::Result<()>
+    where
+        A: FromNapiValue,
+        R: napi::bindgen_prelude::ToNapiValue + Send + 'static,
+        F: Fn(A) -> Fut + Send + 'static,
+        Fut: Future<Output = crate::Result<R>> + Send + 'static,
+    {
+        let handle = ::tokio::runtime::Handle::try_current().map_err(|e| {
+            NodeError::from_kind(ErrorKind::InvalidArgument, e.to_string()).with_source(e)
+        })?;
+        let name = name.to_string();
+        self.exec(move |env| {
+            let function = crate::tokio::create_async_function(env, &name, handle, f)?;
+            env.get_global()?.set_named_property(&name, function)
+        })
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        self.tasks.release();
+        // Without a join, nothing would end an event loop kept alive by a timer or server
+        if self.thread.is_some() {
+            let _ = self.handle.stop();
+        }
+    }
+}
+
+/// The state shared between an [`EvalFuture`] and its [`Completer`].
+struct EvalState<T> {
+    result: Option<crate::Result<T>>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// A future returned by [`Runtime::eval_async`] and [`Runtime::import`].
+/// Resolves to an error if Node.js stops before the result is available.
+pub struct EvalFuture<T> {
+    state: Arc<Mutex<EvalState<T>>>,
+}
+
+impl<T> EvalFuture<T> {
+    fn new() -> (Self, Completer<T>) {
+        let state = Arc::new(Mutex::new(EvalState {
+            result: None,
+            waker: None,
+            closed: false,
+        }));
+
+        (
+            Self {
+                state: state.clone(),
+            },
+            Completer { state },
+        )
+    }
+
+    fn ready(result: crate::Result<T>) -> Self {
+        let (future, completer) = Self::new();
+        completer.complete(result);
+        future
+    }
+}
+
+impl<T> Future for EvalFuture<T> {
+    type Output = crate::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Ok(mut state) = self.state.lock() else {
+            return Poll::Ready(Err(NodeError::from_kind(
+                ErrorKind::LockPoisoned,
+                "Mutex lock failed",
+            )));
+        };
+
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else if state.closed {
+            Poll::Ready(Err(NodeError::from_kind(
+                ErrorKind::NotRunning,
+                "Node.js stopped before the promise settled",
+            )))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Completes an [`EvalFuture`] from the event loop thread.
+/// Dropping it without a result makes the future resolve to an error.
+struct Completer<T> {
+    state: Arc<Mutex<EvalState<T>>>,
+}
+
+impl<T> Completer<T> {
+    fn complete(self, result: crate::Result<T>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.result = Some(result);
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let waker = self.state.lock().ok().and_then(|mut state| {
+            state.closed = true;
+            state.waker.take()
+        });
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The namespace object of a module imported using [`Runtime::import`].
+pub struct ModuleNamespace {
+    reference: Reference,
+    tasks: TaskSender,
+}
+
+impl ModuleNamespace {
+    /// Runs the provided closure on the event loop thread with the namespace object
+    /// and returns its result. Blocks until the closure has been executed.
+    pub fn exec<F, T>(&self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(Env, JsObject) -> napi::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let reference = self.reference;
+        self.tasks.exec(move |env| {
+            let namespace =
+                unsafe { JsObject::from_raw_unchecked(env.raw(), reference.value(env)?) };
+            f(env, namespace)
+        })
+    }
+
+    /// Returns the export with the given name converted to `T`.
+    pub fn get<T>(&self, name: &str) -> crate::Result<T>
+    where
+        T: FromNapiValue + Send + 'static,
+    {
+        let name = name.to_string();
+        self.exec(move |_, namespace| namespace.get_named_property_unchecked(&name))
+    }
+}
+
+impl Drop for ModuleNamespace {
+    fn drop(&mut self) {
+        // If Node.js has stopped, the reference was freed together with the environment
+        let reference = self.reference;
+        let _ = self.tasks.post(Box::new(move |env| reference.delete(env)));
+    }
+}
+
+unsafe fn create_task_queue(env: Env, queue: &SharedQueue) -> napi::Result<TaskQueue> {
+    unsafe extern "C" fn call_js(
+        env: napi_env,
+        _js_callback: napi_value,
+        _context: *mut c_void,
+        data: *mut c_void,
+    ) {
+        let task = Box::from_raw(data as *mut Task);
+        // The environment is null if the queue is drained during teardown.
+        // Dropping the task lets the caller know it was not executed.
+        if !env.is_null() {
+            task(Env::from_raw(env));
+        }
+    }
+
+    unsafe extern "C" fn finalize(_env: napi_env, data: *mut c_void, _hint: *mut c_void) {
+        let queue = Arc::from_raw(data as *const Mutex<Option<TaskQueue>>);
+        let _ = queue.lock().map(|mut queue| queue.take());
+    }
+
+    let resource_name = env.create_string("nodejs::Runtime")?;
+    let finalize_data = Arc::into_raw(queue.clone()) as *mut c_void;
+
+    let mut tsfn = null_mut();
+    let status = napi::sys::napi_create_threadsafe_function(
+        env.raw(),
+        null_mut(),
+        null_mut(),
+        resource_name.raw(),
+        0,
+        1,
+        finalize_data,
+        Some(finalize),
+        null_mut(),
+        Some(call_js),
+        &mut tsfn,
+    );
+
+    if status != napi::sys::Status::napi_ok {
+        drop(Arc::from_raw(
+            finalize_data as *const Mutex<Option<TaskQueue>>,
+        ));
+        return Err(napi::Error::from_status(napi::Status::from(status)));
+    }
+
+    NonNull::new(tsfn)
+        .map(TaskQueue)
+        .ok_or_else(|| napi::Error::from_reason("Failed to create the task queue"))
+}
+
+/// Calls `settled` once the promise is fulfilled or rejected.
+/// If the handlers can't be attached to the promise, `settled` is called right away with
+/// the pending exception, which is cleared, or an error describing the failure.
+/// If the environment is torn down first, `settled` is dropped without being called.
+unsafe fn on_settled(env: Env, promise: JsObject, settled: Settled) {
+    let state = Rc::new(Cell::new(Some(settled)));
+    let Err(err) = attach_settled(env, promise, state.clone()) else {
+        return;
+    };
+
+    if let Some(settled) = state.take() {
+        let mut reason = null_mut();
+        let mut pending = false;
+        napi::sys::napi_is_exception_pending(env.raw(), &mut pending);
+        if pending {
+            napi::sys::napi_get_and_clear_last_exception(env.raw(), &mut reason);
+        } else if let Ok(error) = env.create_error(err) {
+            reason = error.raw();
+        }
+
+        // Without a reason, dropping `settled` reports the failure
+        if !reason.is_null() {
+            settled(env, Err(reason));
+        }
+    }
+}
+
+unsafe fn attach_settled(
+    env: Env,
+    promise: JsObject,
+    state: Rc<Cell<Option<Settled>>>,
+) -> napi::Result<()> {
+    unsafe extern "C" fn settle<const FULFILLED: bool>(
+        env: napi_env,
+        info: napi_callback_info,
+    ) -> napi_value {
+        let mut argc = 1;
+        let mut value = null_mut();
+        let mut data = null_mut();
+        napi::sys::napi_get_cb_info(env, info, &mut argc, &mut value, null_mut(), &mut data);
+
+        let state = &*(data as *const Cell<Option<Settled>>);
+        if let Some(settled) = state.take() {
+            let result = if FULFILLED { Ok(value) } else { Err(value) };
+            settled(Env::from_raw(env), result);
+        }
+
+        null_mut()
+    }
+
+    // The callback is owned by an external value that both handlers keep alive, so it is
+    // dropped once the handlers are garbage collected or the environment is torn down.
+    let data = Rc::as_ptr(&state) as *mut c_void;
+    let state = env.create_external(state, None)?;
+
+    let mut handlers = Vec::with_capacity(2);
+    for callback in [settle::<true>, settle::<false>] {
+        let mut handler = null_mut();
+        napi::check_status!(napi::sys::napi_create_function(
+            env.raw(),
+            null(),
+            0,
+            Some(callback),
+            data,
+            &mut handler,
+        ))?;
+
+        let handler = JsFunction::from_raw_unchecked(env.raw(), handler);
+        JsObject::from_raw_unchecked(env.raw(), handler.raw())
+            .set_named_property("state", &state)?;
+        handlers.push(handler);
+    }
+
+    let then: JsFunction = promise.get_named_property_unchecked("then")?;
+    then.call(Some(&promise), &handlers)?;
+    Ok(())
+}
+
+/// Converts a JavaScript value to `T`, including the exception thrown by the conversion.
+fn from_value<T: FromNapiValue>(env: Env, value: napi_value) -> crate::Result<T> {
+    unsafe { T::from_napi_value(env.raw(), value) }.map_err(|err| to_error(env, err))
+}
+
+/// Converts a failed N-API call to an error, preferring the pending JavaScript exception.
+fn to_error(env: Env, err: napi::Error) -> NodeError {
+    take_exception(env).unwrap_or_else(|| NodeError::generic(err.to_string()))
+}
+
+/// Clears the pending JavaScript exception, if any, and converts it to an error.
+fn take_exception(env: Env) -> Option<NodeError> {
+    let mut pending = false;
+    unsafe { napi::sys::napi_is_exception_pending(env.raw(), &mut pending) };
+    if !pending {
+        return None;
+    }
+
+    let mut exception = null_mut();
+    unsafe { napi::sys::napi_get_and_clear_last_exception(env.raw(), &mut exception) };
+    Some(error_from_value(env, exception))
+}
+
+/// Converts a thrown JavaScript value to an error that carries its stack trace.
+fn error_from_value(env: Env, value: napi_value) -> NodeError {
+    let to_unknown = |value| unsafe { JsUnknown::from_napi_value(env.raw(), value) };
+    let to_string = |value: JsUnknown| {
+        value
+            .coerce_to_string()
+            .and_then(|s| s.into_utf8())
+            .and_then(|s| s.into_owned())
+            .ok()
+    };
+
+    let stack = to_unknown(value)
+        .and_then(|value| value.coerce_to_object())
+        .and_then(|obj| obj.get_named_property::<JsUnknown>("stack"))
+        .ok()
+        .filter(|stack| matches!(stack.get_type(), Ok(napi::ValueType::String)))
+        .and_then(to_string);
+
+    let message = to_unknown(value)
+        .ok()
+        .and_then(to_string)
+        .unwrap_or_else(|| "Unknown JavaScript error".to_string());
+
+    let error = NodeError::from_kind(ErrorKind::Exception, message);
+    match stack {
+        Some(stack) => error.with_stack(stack),
+        None => error,
+    }
+}
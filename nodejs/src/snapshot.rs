@@ -0,0 +1,76 @@
+//! Startup snapshots, see [`Snapshot`].
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::args::NodeArgs;
+use crate::error::{ErrorKind, NodeError};
+
+/// The state of a Node.js environment after a script has run. Later runs can start from
+/// it instead of bootstrapping Node.js and loading their modules again,
+/// see [`NodeArgs::snapshot`].
+///
+/// A snapshot can only be used with the Node.js build and platform that created it.
+/// This is checked when it is loaded, and returns an error of the kind
+/// [`ErrorKind::InvalidSnapshot`] otherwise.
+#[derive(Clone)]
+pub struct Snapshot(Arc<[u8]>);
+
+impl Snapshot {
+    /// Runs `script` until the event loop has no more work to do, and creates a snapshot
+    /// of the environment.
+    ///
+    /// The script runs without the module init function and the Rust bindings of a run.
+    /// Its `require` only loads built-in modules, and its state must be stored on
+    /// `globalThis` to be part of the snapshot. `require('v8').startupSnapshot` can be used
+    /// to run code when the snapshot is serialized or deserialized.
+    ///
+    /// Only the arguments and environment variables of `args` are used.
+    /// Initializes Node.js like the first run in a process.
+    pub fn create<S: Into<String>>(script: S, args: Option<NodeArgs>) -> crate::Result<Self> {
+        crate::raw::create_snapshot(script.into(), args).map(|data| Self(data.into()))
+    }
+
+    /// Loads a snapshot created by [`Snapshot::create`] from its bytes.
+    pub fn from_bytes<B: Into<Vec<u8>>>(bytes: B) -> crate::Result<Self> {
+        let bytes = bytes.into();
+        match crate::raw::check_snapshot(&bytes) {
+            Some(error) => Err(NodeError::from_kind(ErrorKind::InvalidSnapshot, error)),
+            None => Ok(Self(bytes.into())),
+        }
+    }
+
+    /// Loads a snapshot written by [`Snapshot::write`].
+    pub fn read<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let bytes = std::fs::read(path.as_ref()).map_err(|e| {
+            NodeError::generic(format!(
+                "Failed to read the snapshot {}: {e}",
+                path.as_ref().display()
+            ))
+            .with_source(e)
+        })?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        std::fs::write(path.as_ref(), &self.0).map_err(|e| {
+            NodeError::generic(format!(
+                "Failed to write the snapshot {}: {e}",
+                path.as_ref().display()
+            ))
+            .with_source(e)
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
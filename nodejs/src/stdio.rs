@@ -0,0 +1,298 @@
+//! Redirecting the standard streams of Node.js to Rust.
+
+use std::ffi::c_void;
+use std::io::{Read, Write};
+use std::os::raw::{c_char, c_int};
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::args::NodeArgs;
+
+/// A destination for the data written to `process.stdout` or `process.stderr`,
+/// see [`NodeArgs::stdout`] and [`NodeArgs::stderr`].
+///
+/// Writes are passed to the sink synchronously on the event loop thread,
+/// in the order they were made, and the sink is flushed after each write.
+/// A failed write emits an `error` event on the JavaScript stream.
+#[derive(Clone)]
+pub struct OutputSink(Arc<Mutex<dyn Write + Send>>);
+
+impl OutputSink {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+
+    /// Calls `f` with every chunk that is written.
+    pub fn from_fn<F: FnMut(&[u8]) + Send + 'static>(f: F) -> Self {
+        Self::new(FnWriter(f))
+    }
+
+    fn write(&self, chunk: &[u8]) -> std::io::Result<()> {
+        let mut writer = self
+            .0
+            .lock()
+            .map_err(|_| std::io::Error::other("Mutex lock failed"))?;
+        writer.write_all(chunk)?;
+        writer.flush()
+    }
+}
+
+impl std::fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputSink").finish_non_exhaustive()
+    }
+}
+
+impl From<OutputBuffer> for OutputSink {
+    fn from(buffer: OutputBuffer) -> Self {
+        Self::new(buffer)
+    }
+}
+
+struct FnWriter<F>(F);
+
+impl<F: FnMut(&[u8])> Write for FnWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        (self.0)(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An in-memory sink. Clones share the same buffer, so the output
+/// can be read while or after Node.js runs.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the data written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().map(|data| data.clone()).unwrap_or_default()
+    }
+
+    /// Returns the data written so far as a string, replacing invalid UTF-8.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.contents()).into_owned()
+    }
+
+    /// Returns the data written so far and empties the buffer.
+    pub fn take(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .map(|mut data| std::mem::take(&mut *data))
+            .unwrap_or_default()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| std::io::Error::other("Mutex lock failed"))?
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The sinks of a run, passed as the data of [`Outputs::callback`].
+pub(crate) struct Outputs {
+    stdout: Option<OutputSink>,
+    stderr: Option<OutputSink>,
+}
+
+impl Outputs {
+    pub(crate) fn new(args: &NodeArgs) -> Self {
+        Self {
+            stdout: args.stdout.clone(),
+            stderr: args.stderr.clone(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none()
+    }
+
+    /// The `node_output_callback_t` that writes to the sinks.
+    pub(crate) unsafe extern "C" fn callback(
+        data: *mut c_void,
+        fd: c_int,
+        chunk: *const c_char,
+        length: usize,
+    ) -> c_int {
+        let outputs = &*(data as *const Outputs);
+        let sink = match fd {
+            1 => outputs.stdout.as_ref(),
+            2 => outputs.stderr.as_ref(),
+            _ => None,
+        };
+        let Some(sink) = sink else {
+            return -1;
+        };
+
+        let chunk = if length == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(chunk as *const u8, length)
+        };
+        match std::panic::catch_unwind(AssertUnwindSafe(|| sink.write(chunk))) {
+            Ok(Ok(())) => 0,
+            _ => -1,
+        }
+    }
+}
+
+/// A source for `process.stdin`, see [`NodeArgs::stdin`].
+///
+/// The source is read on a separate thread, one chunk at a time, only while
+/// JavaScript reads `process.stdin`. Reading keeps the event loop alive until
+/// the source ends, like the stdin of the `node` executable.
+/// Clones share the same source, so a later run continues where the last one stopped.
+/// A chunk that was read, but not passed to JavaScript before the run ended, is kept
+/// for the next run. If a read is still blocked when the run ends, the next run waits
+/// for it to return.
+#[derive(Clone)]
+pub struct InputSource(Arc<Mutex<SourceState>>);
+
+struct SourceState {
+    reader: Box<dyn Read + Send>,
+    /// Read by an earlier run, returned before reading from `reader` again.
+    unread: Vec<u8>,
+}
+
+impl InputSource {
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        Self(Arc::new(Mutex::new(SourceState {
+            reader: Box::new(reader),
+            unread: Vec::new(),
+        })))
+    }
+
+    /// Reads the chunks sent to the channel. The input ends once all senders are dropped.
+    pub fn from_channel(receiver: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self::new(ChannelReader {
+            receiver,
+            chunk: Vec::new(),
+            offset: 0,
+        })
+    }
+
+    /// Reads from an async reader by blocking on the given tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn from_async_read<R>(reader: R, handle: ::tokio::runtime::Handle) -> Self
+    where
+        R: ::tokio::io::AsyncRead + Unpin + Send + 'static,
+    {
+        Self::new(AsyncReader { reader, handle })
+    }
+
+    fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut state = self
+            .0
+            .lock()
+            .map_err(|_| std::io::Error::other("Mutex lock failed"))?;
+        if !state.unread.is_empty() {
+            let length = buf.len().min(state.unread.len());
+            buf[..length].copy_from_slice(&state.unread[..length]);
+            state.unread.drain(..length);
+            return Ok(length);
+        }
+
+        loop {
+            match state.reader.read(buf) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// The `node_input_callback_t` that reads from the source.
+    pub(crate) unsafe extern "C" fn callback(
+        data: *mut c_void,
+        buffer: *mut c_char,
+        length: usize,
+    ) -> c_int {
+        let source = &*(data as *const InputSource);
+        let buf = std::slice::from_raw_parts_mut(buffer as *mut u8, length);
+        match std::panic::catch_unwind(AssertUnwindSafe(|| source.read(buf))) {
+            Ok(Ok(read)) => read as c_int,
+            _ => -1,
+        }
+    }
+
+    /// The `node_input_unread_t` that keeps a chunk for the next read.
+    pub(crate) unsafe extern "C" fn unread(data: *mut c_void, chunk: *const c_char, length: usize) {
+        let source = &*(data as *const InputSource);
+        let chunk = std::slice::from_raw_parts(chunk as *const u8, length);
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Ok(mut state) = source.0.lock() {
+                state.unread.splice(0..0, chunk.iter().copied());
+            }
+        }));
+    }
+
+    /// The `node_input_release_t` that frees the data created by [`InputSource::into_raw`].
+    pub(crate) unsafe extern "C" fn release(data: *mut c_void) {
+        drop(Box::from_raw(data as *mut InputSource));
+    }
+
+    pub(crate) fn into_raw(self) -> *mut c_void {
+        Box::into_raw(Box::new(self)) as *mut c_void
+    }
+}
+
+impl std::fmt::Debug for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InputSource").finish_non_exhaustive()
+    }
+}
+
+struct ChannelReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let read = buf.len().min(self.chunk.len() - self.offset);
+        buf[..read].copy_from_slice(&self.chunk[self.offset..self.offset + read]);
+        self.offset += read;
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "tokio")]
+struct AsyncReader<R> {
+    reader: R,
+    handle: ::tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: ::tokio::io::AsyncRead + Unpin> Read for AsyncReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use ::tokio::io::AsyncReadExt;
+
+        self.handle.block_on(self.reader.read(buf))
+    }
+}
@@ -1,3 +1,9 @@
 #![allow(deref_nullptr)]
 
 include!(concat!(env!("OUT_DIR"), "/sys.rs"));
+
+// A libnode built from another header, e.g. through LIBNODE_PATH, would not match the bindings
+const _: () = assert!(
+    NODE_EMBEDDING_API_VERSION == 1,
+    "libnode was built for another version of the embedding API"
+);
@@ -0,0 +1,295 @@
+//! Running async Rust functions from JavaScript on a tokio runtime.
+//!
+//! Each call of a function created by [`create_async_function`] spawns a task on the
+//! tokio runtime and returns a promise that settles with the result of the task.
+//! Pending tasks keep the Node.js event loop alive.
+//!
+//! If Node.js is stopped, for example using [`crate::raw::stop`], pending tasks are aborted.
+//! If the tokio runtime shuts down first, the pending promises are rejected.
+
+use std::ffi::c_void;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+
+use ::tokio::runtime::Handle;
+use ::tokio::task::AbortHandle;
+use napi::bindgen_prelude::{FromNapiValue, ToNapiValue};
+use napi::sys::{
+    napi_callback_info, napi_deferred, napi_env, napi_threadsafe_function, napi_value,
+};
+use napi::{Env, JsFunction, NapiRaw, NapiValue};
+
+use crate::error::NodeError;
+
+type PendingTasks = Arc<Mutex<Vec<AbortHandle>>>;
+
+/// The state of a function created by [`create_async_function`].
+struct AsyncFunction<F> {
+    f: F,
+    handle: Handle,
+    tasks: PendingTasks,
+}
+
+/// Creates a JavaScript function that calls `f` with its first argument converted to `A`,
+/// spawns the returned future on the tokio runtime and returns a promise.
+/// The promise is resolved with the output of the future converted to a JavaScript
+/// value, or rejected with an `Error` if the future returns an error.
+///
+/// `f` itself is called on the event loop thread.
+pub fn create_async_function<A, R, F, Fut>(
+    env: Env,
+    name: &str,
+    handle: Handle,
+    f: F,
+) -> napi::Result<JsFunction>
+where
+    A: FromNapiValue,
+    R: ToNapiValue + Send + 'static,
+    F: Fn(A) -> Fut + 'static,
+    Fut: Future<Output = crate::Result<R>> + Send + 'static,
+{
+    unsafe extern "C" fn call<A, R, F, Fut>(env: napi_env, info: napi_callback_info) -> napi_value
+    where
+        A: FromNapiValue,
+        R: ToNapiValue + Send + 'static,
+        F: Fn(A) -> Fut + 'static,
+        Fut: Future<Output = crate::Result<R>> + Send + 'static,
+    {
+        let mut argc = 1;
+        let mut arg = null_mut();
+        let mut data = null_mut();
+        napi::sys::napi_get_cb_info(env, info, &mut argc, &mut arg, null_mut(), &mut data);
+
+        let mut deferred = null_mut();
+        let mut promise = null_mut();
+        if napi::sys::napi_create_promise(env, &mut deferred, &mut promise)
+            != napi::sys::Status::napi_ok
+        {
+            return null_mut();
+        }
+
+        let function = &*(data as *const AsyncFunction<F>);
+        if let Err(err) = spawn_call::<A, R, F, Fut>(env, function, arg, deferred) {
+            reject(env, deferred, &err.to_string());
+        }
+
+        promise
+    }
+
+    unsafe extern "C" fn finalize<F>(_env: napi_env, data: *mut c_void, _hint: *mut c_void) {
+        drop(Box::from_raw(data as *mut AsyncFunction<F>));
+    }
+
+    unsafe extern "C" fn abort_tasks(data: *mut c_void) {
+        let tasks = Arc::from_raw(data as *const Mutex<Vec<AbortHandle>>);
+        let _ = tasks
+            .lock()
+            .map(|tasks| tasks.iter().for_each(AbortHandle::abort));
+    }
+
+    let tasks = PendingTasks::default();
+    let data = Box::into_raw(Box::new(AsyncFunction {
+        f,
+        handle,
+        tasks: tasks.clone(),
+    }));
+
+    unsafe {
+        let mut function = null_mut();
+        let status = napi::sys::napi_create_function(
+            env.raw(),
+            name.as_ptr().cast(),
+            name.len(),
+            Some(call::<A, R, F, Fut>),
+            data.cast(),
+            &mut function,
+        );
+        if status != napi::sys::Status::napi_ok {
+            drop(Box::from_raw(data));
+            return Err(napi::Error::from_status(napi::Status::from(status)));
+        }
+
+        // Frees the state once the function is garbage collected
+        napi::check_status!(napi::sys::napi_wrap(
+            env.raw(),
+            function,
+            data.cast(),
+            Some(finalize::<F>),
+            null_mut(),
+            null_mut(),
+        ))?;
+
+        napi::check_status!(napi::sys::napi_add_env_cleanup_hook(
+            env.raw(),
+            Some(abort_tasks),
+            Arc::into_raw(tasks) as *mut c_void,
+        ))?;
+
+        Ok(JsFunction::from_raw_unchecked(env.raw(), function))
+    }
+}
+
+unsafe fn spawn_call<A, R, F, Fut>(
+    env: napi_env,
+    function: &AsyncFunction<F>,
+    arg: napi_value,
+    deferred: napi_deferred,
+) -> napi::Result<()>
+where
+    A: FromNapiValue,
+    R: ToNapiValue + Send + 'static,
+    F: Fn(A) -> Fut + 'static,
+    Fut: Future<Output = crate::Result<R>> + Send + 'static,
+{
+    let arg = A::from_napi_value(env, arg)?;
+    let completion = Completion::<R>::new(Env::from_raw(env), deferred)?;
+    let future = (function.f)(arg);
+
+    let task = function.handle.spawn(async move {
+        completion.complete(future.await);
+    });
+
+    if let Ok(mut tasks) = function.tasks.lock() {
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task.abort_handle());
+    }
+
+    Ok(())
+}
+
+/// A thread-safe function, set to `None` once it has been released or finalized.
+/// Node.js frees thread-safe functions when the environment is torn down,
+/// so they must not be used after that.
+type SharedTsfn = Arc<Mutex<Option<Tsfn>>>;
+
+struct Tsfn(napi_threadsafe_function);
+
+// N-API allows calling and releasing thread-safe functions from any thread.
+unsafe impl Send for Tsfn {}
+
+/// Settles the promise of a call from the tokio runtime.
+/// The thread-safe function keeps the event loop alive until the result has been sent.
+struct Completion<R: ToNapiValue + Send + 'static> {
+    tsfn: SharedTsfn,
+    _result: PhantomData<fn(R)>,
+}
+
+impl<R: ToNapiValue + Send + 'static> Completion<R> {
+    fn new(env: Env, deferred: napi_deferred) -> napi::Result<Self> {
+        unsafe extern "C" fn call_js<R: ToNapiValue>(
+            env: napi_env,
+            _js_callback: napi_value,
+            context: *mut c_void,
+            data: *mut c_void,
+        ) {
+            let result = Box::from_raw(data as *mut crate::Result<R>);
+            // The environment is null if Node.js is stopping, the promise is discarded then
+            if env.is_null() {
+                return;
+            }
+
+            let deferred = context as napi_deferred;
+            match (*result).and_then(|value| {
+                R::to_napi_value(env, value).map_err(|e| NodeError::generic(e.to_string()))
+            }) {
+                Ok(value) => {
+                    napi::sys::napi_resolve_deferred(env, deferred, value);
+                }
+                Err(err) => reject(env, deferred, err.message()),
+            }
+        }
+
+        unsafe extern "C" fn finalize(_env: napi_env, data: *mut c_void, _hint: *mut c_void) {
+            let tsfn = Arc::from_raw(data as *const Mutex<Option<Tsfn>>);
+            let _ = tsfn.lock().map(|mut tsfn| tsfn.take());
+        }
+
+        let tsfn = SharedTsfn::default();
+        let resource_name = env.create_string("nodejs::tokio")?;
+        let finalize_data = Arc::into_raw(tsfn.clone()) as *mut c_void;
+
+        let mut raw_tsfn = null_mut();
+        let status = unsafe {
+            napi::sys::napi_create_threadsafe_function(
+                env.raw(),
+                null_mut(),
+                null_mut(),
+                resource_name.raw(),
+                0,
+                1,
+                finalize_data,
+                Some(finalize),
+                deferred.cast(),
+                Some(call_js::<R>),
+                &mut raw_tsfn,
+            )
+        };
+
+        if status != napi::sys::Status::napi_ok {
+            drop(unsafe { Arc::from_raw(finalize_data as *const Mutex<Option<Tsfn>>) });
+            return Err(napi::Error::from_status(napi::Status::from(status)));
+        }
+
+        if let Ok(mut shared) = tsfn.lock() {
+            *shared = Some(Tsfn(raw_tsfn));
+        }
+
+        Ok(Self {
+            tsfn,
+            _result: PhantomData,
+        })
+    }
+
+    fn complete(self, result: crate::Result<R>) {
+        self.send(result);
+    }
+
+    fn send(&self, result: crate::Result<R>) {
+        // The lock is held until the call is done, so the function can't be finalized meanwhile
+        let Ok(mut shared) = self.tsfn.lock() else {
+            return;
+        };
+        let Some(tsfn) = shared.take() else {
+            return;
+        };
+
+        let data = Box::into_raw(Box::new(result));
+        unsafe {
+            let status = napi::sys::napi_call_threadsafe_function(
+                tsfn.0,
+                data.cast(),
+                napi::sys::ThreadsafeFunctionCallMode::nonblocking,
+            );
+            if status != napi::sys::Status::napi_ok {
+                drop(Box::from_raw(data));
+            }
+
+            napi::sys::napi_release_threadsafe_function(
+                tsfn.0,
+                napi::sys::ThreadsafeFunctionReleaseMode::release,
+            );
+        }
+    }
+}
+
+impl<R: ToNapiValue + Send + 'static> Drop for Completion<R> {
+    fn drop(&mut self) {
+        // The task was aborted or the tokio runtime shut down before it completed
+        self.send(Err(NodeError::generic("The task was cancelled")));
+    }
+}
+
+unsafe fn reject(env: napi_env, deferred: napi_deferred, message: &str) {
+    let mut message_value = null_mut();
+    let mut error = null_mut();
+    napi::sys::napi_create_string_utf8(
+        env,
+        message.as_ptr().cast(),
+        message.len(),
+        &mut message_value,
+    );
+    napi::sys::napi_create_error(env, null_mut(), message_value, &mut error);
+    napi::sys::napi_reject_deferred(env, deferred, error);
+}